@@ -0,0 +1,208 @@
+//! An in-memory backend with a few simulated monitors, for working on the
+//! UI without a running compositor.
+
+use druid::im::Vector;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{BackendError, Change, OutputBackend, OutputsCallback};
+use crate::model::display::{Display, Mode, Scale};
+
+/// How often the simulated external monitor is plugged in or unplugged.
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The connector of the output which is repeatedly plugged in and unplugged.
+const HOTPLUG_OUTPUT: &str = "HDMI-A-1";
+
+#[derive(Default)]
+struct State {
+    outputs: Vec<Display>,
+    unplugged: Vec<Display>,
+    listeners: Vec<Sender<()>>,
+}
+
+impl State {
+    fn changed(&mut self) {
+        self.listeners.retain(|l| l.send(()).is_ok());
+    }
+}
+
+#[derive(Clone)]
+pub struct FakeBackend {
+    state: Arc<Mutex<State>>,
+}
+
+fn fake_display(
+    name: &str,
+    make: &str,
+    model: &str,
+    modes: &[(i32, i32, i32)],
+    x: i32,
+    scale: f64,
+) -> Display {
+    let modes: Vector<Mode> = modes
+        .iter()
+        .enumerate()
+        .map(|(i, (width, height, refresh))| Mode {
+            width: *width,
+            height: *height,
+            refresh: *refresh,
+            selected: i == 0,
+            ..Mode::default()
+        })
+        .collect();
+
+    let mut d = Display {
+        name: name.to_string(),
+        make: make.to_string(),
+        model: model.to_string(),
+        serial: format!("FAKE-{}", name),
+        active: true,
+        position: (x, 0).into(),
+        scale: Scale(scale),
+        modes,
+        ..Display::default()
+    };
+    d.size = d.logical_size();
+    d
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        let outputs = vec![
+            fake_display(
+                "eDP-1",
+                "Fake Panels",
+                "Laptop 14",
+                &[(1920, 1200, 60000), (1280, 800, 60000)],
+                0,
+                1.25,
+            ),
+            fake_display(
+                "DP-1",
+                "Fake Displays Inc.",
+                "FD2720Q",
+                &[
+                    (2560, 1440, 143912),
+                    (2560, 1440, 59951),
+                    (1920, 1080, 60000),
+                ],
+                1536,
+                1.,
+            ),
+            fake_display(
+                HOTPLUG_OUTPUT,
+                "Fake Displays Inc.",
+                "FD2410",
+                &[(1920, 1080, 60000), (1280, 720, 60000)],
+                4096,
+                1.,
+            ),
+        ];
+
+        FakeBackend {
+            state: Arc::new(Mutex::new(State {
+                outputs,
+                ..State::default()
+            })),
+        }
+    }
+
+    /// Connects a simulated output.
+    pub fn plug(&self, display: Display) {
+        let mut state = self.state.lock().unwrap();
+        state.outputs.retain(|o| o.name != display.name);
+        state.outputs.push(display);
+        state.changed();
+    }
+
+    /// Disconnects a simulated output, returning it.
+    pub fn unplug(&self, name: &str) -> Option<Display> {
+        let mut state = self.state.lock().unwrap();
+        let i = state.outputs.iter().position(|o| o.name == name)?;
+        let display = state.outputs.remove(i);
+        state.changed();
+        Some(display)
+    }
+
+    fn toggle_hotplug(&self) {
+        match self.unplug(HOTPLUG_OUTPUT) {
+            Some(d) => self.state.lock().unwrap().unplugged.push(d),
+            None => {
+                let d = {
+                    let mut state = self.state.lock().unwrap();
+                    let i = state
+                        .unplugged
+                        .iter()
+                        .position(|o| o.name == HOTPLUG_OUTPUT);
+                    i.map(|i| state.unplugged.remove(i))
+                };
+                if let Some(d) = d {
+                    self.plug(d);
+                }
+            }
+        }
+    }
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        FakeBackend::new()
+    }
+}
+
+impl OutputBackend for FakeBackend {
+    fn outputs(&mut self) -> Result<Vec<Display>, BackendError> {
+        Ok(self.state.lock().unwrap().outputs.clone())
+    }
+
+    fn apply(&mut self, changes: &[Change]) -> Result<(), BackendError> {
+        let mut state = self.state.lock().unwrap();
+        for c in changes {
+            let want = &c.want;
+            if want.scale.0.is_nan() || want.scale.0 <= 0. {
+                return Err(BackendError(format!(
+                    "invalid scale {} for {}",
+                    want.scale, want.name
+                )));
+            }
+
+            let output = state
+                .outputs
+                .iter_mut()
+                .find(|o| o.name == want.name)
+                .ok_or_else(|| BackendError(format!("no output named {:?}", want.name)))?;
+
+            let mut applied = want.clone();
+            applied.focused = false;
+            applied.size = applied.logical_size();
+            *output = applied;
+        }
+        state.changed();
+        Ok(())
+    }
+
+    fn subscribe(&self, mut on_change: OutputsCallback) {
+        let (tx, rx) = mpsc::channel();
+        self.state.lock().unwrap().listeners.push(tx);
+
+        let backend = self.clone();
+        std::thread::spawn(move || loop {
+            match rx.recv_timeout(HOTPLUG_INTERVAL) {
+                Ok(()) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    // toggle_hotplug notifies us through the channel.
+                    backend.toggle_hotplug();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let outputs = backend.state.lock().unwrap().outputs.clone();
+            if !on_change(Ok(outputs)) {
+                break;
+            }
+        });
+    }
+}
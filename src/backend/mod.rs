@@ -0,0 +1,87 @@
+//! Backends which list and configure the outputs of the compositor.
+
+use crate::model::display::Display;
+
+pub mod fake;
+pub mod sway;
+pub mod wlr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackendError(pub String);
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<swayipc::Error> for BackendError {
+    fn from(e: swayipc::Error) -> Self {
+        BackendError(e.to_string())
+    }
+}
+
+/// A change to one output: its current state, and the state wanted.
+#[derive(Clone)]
+pub struct Change {
+    pub live: Display,
+    pub want: Display,
+}
+
+/// Called from a background thread with the new list of outputs whenever
+/// they change. Returning false stops the subscription.
+pub type OutputsCallback = Box<dyn FnMut(Result<Vec<Display>, BackendError>) -> bool + Send>;
+
+pub trait OutputBackend {
+    /// Lists the outputs, including disabled ones.
+    fn outputs(&mut self) -> Result<Vec<Display>, BackendError>;
+
+    /// Changes the outputs to their wanted state.
+    fn apply(&mut self, changes: &[Change]) -> Result<(), BackendError>;
+
+    /// Starts watching for outputs being plugged in, unplugged or changed.
+    fn subscribe(&self, on_change: OutputsCallback);
+
+    /// Names of the workspaces the compositor currently has. Compositors
+    /// without workspaces we can list report none.
+    fn workspaces(&mut self) -> Result<Vec<String>, BackendError> {
+        Ok(Vec::new())
+    }
+
+    /// Moves the window with the given title onto an output, centered.
+    /// Compositors which don't let clients do this leave the window where
+    /// it is.
+    fn place_window(&mut self, _title: &str, _output: &str) -> Result<(), BackendError> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackendKind {
+    Sway,
+    Wlr,
+    Fake,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = BackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sway" => Ok(BackendKind::Sway),
+            "wlr" => Ok(BackendKind::Wlr),
+            "fake" => Ok(BackendKind::Fake),
+            _ => Err(BackendError(format!("unknown backend {:?}", s))),
+        }
+    }
+}
+
+pub fn open(kind: BackendKind) -> Box<dyn OutputBackend + Send> {
+    match kind {
+        BackendKind::Sway => Box::new(sway::SwayBackend::default()),
+        BackendKind::Wlr => Box::new(wlr::WlrBackend),
+        BackendKind::Fake => Box::new(fake::FakeBackend::new()),
+    }
+}
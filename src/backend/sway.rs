@@ -0,0 +1,105 @@
+use swayipc::{Connection, EventType};
+
+use super::{BackendError, Change, OutputBackend, OutputsCallback};
+use crate::model::display::Display;
+
+/// Configures outputs over sway IPC.
+#[derive(Default)]
+pub struct SwayBackend {
+    /// Connected on first use, so that the UI can still start without sway.
+    conn: Option<Connection>,
+}
+
+impl SwayBackend {
+    fn conn(&mut self) -> Result<&mut Connection, BackendError> {
+        if self.conn.is_none() {
+            self.conn = Some(Connection::new()?);
+        }
+        Ok(self.conn.as_mut().unwrap())
+    }
+}
+
+impl OutputBackend for SwayBackend {
+    fn outputs(&mut self) -> Result<Vec<Display>, BackendError> {
+        Ok(self
+            .conn()?
+            .get_outputs()?
+            .into_iter()
+            .map(|o| o.into())
+            .collect())
+    }
+
+    fn apply(&mut self, changes: &[Change]) -> Result<(), BackendError> {
+        let conn = self.conn()?;
+        for c in changes {
+            if let Some(cmd) = c.want.apply_command(&c.live) {
+                for r in conn.run_command(cmd)? {
+                    r?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn workspaces(&mut self) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .conn()?
+            .get_workspaces()?
+            .into_iter()
+            .map(|w| w.name)
+            .collect())
+    }
+
+    fn place_window(&mut self, title: &str, output: &str) -> Result<(), BackendError> {
+        let mut escaped = String::new();
+        for c in title.chars() {
+            if "\\.+*?()|[]{}^$\"".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        let cmd = format!(
+            "[title=\"^{}$\"] floating enable, move container to output \"{}\", move position center",
+            escaped, output
+        );
+
+        for r in self.conn()?.run_command(cmd)? {
+            r?;
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self, mut on_change: OutputsCallback) {
+        std::thread::spawn(move || {
+            let events = match Connection::new().and_then(|c| c.subscribe([EventType::Output])) {
+                Ok(events) => events,
+                Err(e) => {
+                    on_change(Err(e.into()));
+                    return;
+                }
+            };
+            let mut conn = match Connection::new() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    on_change(Err(e.into()));
+                    return;
+                }
+            };
+
+            for event in events {
+                if let Err(e) = event {
+                    on_change(Err(e.into()));
+                    break;
+                }
+
+                let outputs = conn
+                    .get_outputs()
+                    .map(|o| o.into_iter().map(|o| o.into()).collect())
+                    .map_err(|e| e.into());
+                if !on_change(outputs) {
+                    break;
+                }
+            }
+        });
+    }
+}
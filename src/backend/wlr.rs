@@ -0,0 +1,428 @@
+//! Configures outputs through the `zwlr_output_manager_v1` Wayland protocol,
+//! for compositors other than sway such as river or Hyprland. Sway implements
+//! it too, so a headless sway (`WLR_BACKENDS=headless sway`) can be used to
+//! try it out with `--backend wlr`.
+
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+use wayland_client::{DispatchData, Display as WlDisplay, EventQueue, GlobalManager, Main};
+use wayland_protocols::wlr::unstable::output_management::v1::client::{
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+
+use super::{BackendError, Change, OutputBackend, OutputsCallback};
+use crate::model::display::{Display, Mode, Scale, Transform};
+
+struct WlrMode {
+    proxy: ZwlrOutputModeV1,
+    width: i32,
+    height: i32,
+    refresh: i32,
+    preferred: bool,
+}
+
+struct Head {
+    proxy: ZwlrOutputHeadV1,
+    name: String,
+    make: String,
+    model: String,
+    serial: String,
+    enabled: bool,
+    modes: Vec<WlrMode>,
+    current_mode: Option<ZwlrOutputModeV1>,
+    position: (i32, i32),
+    transform: Transform,
+    scale: f64,
+}
+
+impl Head {
+    fn new(proxy: ZwlrOutputHeadV1) -> Self {
+        Head {
+            proxy,
+            name: String::new(),
+            make: String::new(),
+            model: String::new(),
+            serial: String::new(),
+            enabled: false,
+            modes: Vec::new(),
+            current_mode: None,
+            position: (0, 0),
+            transform: Transform::None,
+            scale: 1.,
+        }
+    }
+
+    fn display(&self) -> Display {
+        let modes = self
+            .modes
+            .iter()
+            .map(|m| Mode {
+                width: m.width,
+                height: m.height,
+                refresh: m.refresh,
+                selected: self.enabled && self.current_mode.as_ref() == Some(&m.proxy),
+                ..Mode::default()
+            })
+            .collect();
+
+        let mut d = Display {
+            name: self.name.clone(),
+            make: self.make.clone(),
+            model: self.model.clone(),
+            serial: self.serial.clone(),
+            active: self.enabled,
+            position: self.position.into(),
+            scale: Scale(self.scale),
+            transform: self.transform,
+            modes,
+            ..Display::default()
+        };
+        d.size = match self
+            .modes
+            .iter()
+            .find(|m| m.preferred)
+            .or(self.modes.first())
+        {
+            Some(m) if !self.enabled => (m.width as u32, m.height as u32),
+            _ => d.logical_size(),
+        };
+        d
+    }
+}
+
+#[derive(Default)]
+struct State {
+    heads: Vec<Head>,
+    /// Serial of the last complete description of the heads.
+    serial: Option<u32>,
+    /// Set when the heads changed, until the change is reported.
+    changed: bool,
+    /// Outcome of the last configuration sent to the compositor.
+    result: Option<Result<(), String>>,
+}
+
+fn transform_from_wl(t: WlTransform) -> Transform {
+    match t {
+        WlTransform::_90 => Transform::R90,
+        WlTransform::_180 => Transform::R180,
+        WlTransform::_270 => Transform::R270,
+        WlTransform::Flipped => Transform::Flipped,
+        WlTransform::Flipped90 => Transform::Flipped90,
+        WlTransform::Flipped180 => Transform::Flipped180,
+        WlTransform::Flipped270 => Transform::Flipped270,
+        _ => Transform::None,
+    }
+}
+
+fn transform_to_wl(t: Transform) -> WlTransform {
+    match t {
+        Transform::None => WlTransform::Normal,
+        Transform::R90 => WlTransform::_90,
+        Transform::R180 => WlTransform::_180,
+        Transform::R270 => WlTransform::_270,
+        Transform::Flipped => WlTransform::Flipped,
+        Transform::Flipped90 => WlTransform::Flipped90,
+        Transform::Flipped180 => WlTransform::Flipped180,
+        Transform::Flipped270 => WlTransform::Flipped270,
+    }
+}
+
+fn mode_event(
+    mode: Main<ZwlrOutputModeV1>,
+    event: zwlr_output_mode_v1::Event,
+    mut ddata: DispatchData,
+) {
+    let state = ddata.get::<State>().unwrap();
+    let m = match state
+        .heads
+        .iter_mut()
+        .flat_map(|h| h.modes.iter_mut())
+        .find(|m| m.proxy == **mode)
+    {
+        Some(m) => m,
+        None => return,
+    };
+
+    match event {
+        zwlr_output_mode_v1::Event::Size { width, height } => {
+            m.width = width;
+            m.height = height;
+        }
+        zwlr_output_mode_v1::Event::Refresh { refresh } => m.refresh = refresh,
+        zwlr_output_mode_v1::Event::Preferred => m.preferred = true,
+        zwlr_output_mode_v1::Event::Finished => {
+            for h in state.heads.iter_mut() {
+                h.modes.retain(|m| m.proxy != **mode);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn head_event(
+    head: Main<ZwlrOutputHeadV1>,
+    event: zwlr_output_head_v1::Event,
+    mut ddata: DispatchData,
+) {
+    let state = ddata.get::<State>().unwrap();
+    let h = match state.heads.iter_mut().find(|h| h.proxy == **head) {
+        Some(h) => h,
+        None => return,
+    };
+
+    match event {
+        zwlr_output_head_v1::Event::Name { name } => h.name = name,
+        zwlr_output_head_v1::Event::Make { make } => h.make = make,
+        zwlr_output_head_v1::Event::Model { model } => h.model = model,
+        zwlr_output_head_v1::Event::SerialNumber { serial_number } => h.serial = serial_number,
+        zwlr_output_head_v1::Event::Enabled { enabled } => h.enabled = enabled != 0,
+        zwlr_output_head_v1::Event::Mode { mode } => {
+            mode.quick_assign(mode_event);
+            h.modes.push(WlrMode {
+                proxy: (**mode).clone(),
+                width: 0,
+                height: 0,
+                refresh: 0,
+                preferred: false,
+            });
+        }
+        zwlr_output_head_v1::Event::CurrentMode { mode } => h.current_mode = Some(mode),
+        zwlr_output_head_v1::Event::Position { x, y } => h.position = (x, y),
+        zwlr_output_head_v1::Event::Transform { transform } => {
+            h.transform = transform_from_wl(transform)
+        }
+        zwlr_output_head_v1::Event::Scale { scale } => h.scale = scale,
+        zwlr_output_head_v1::Event::Finished => {
+            state.heads.retain(|h| h.proxy != **head);
+        }
+        _ => {}
+    }
+}
+
+fn manager_event(
+    _: Main<ZwlrOutputManagerV1>,
+    event: zwlr_output_manager_v1::Event,
+    mut ddata: DispatchData,
+) {
+    let state = ddata.get::<State>().unwrap();
+    match event {
+        zwlr_output_manager_v1::Event::Head { head } => {
+            head.quick_assign(head_event);
+            state.heads.push(Head::new((**head).clone()));
+        }
+        zwlr_output_manager_v1::Event::Done { serial } => {
+            state.serial = Some(serial);
+            state.changed = true;
+        }
+        _ => {}
+    }
+}
+
+fn configuration_event(
+    _: Main<ZwlrOutputConfigurationV1>,
+    event: zwlr_output_configuration_v1::Event,
+    mut ddata: DispatchData,
+) {
+    let state = ddata.get::<State>().unwrap();
+    state.result = match event {
+        zwlr_output_configuration_v1::Event::Succeeded => Some(Ok(())),
+        zwlr_output_configuration_v1::Event::Failed => {
+            Some(Err("the compositor rejected the configuration".into()))
+        }
+        zwlr_output_configuration_v1::Event::Cancelled => Some(Err(
+            "the outputs changed while applying the configuration".into(),
+        )),
+        _ => return,
+    };
+}
+
+fn io_error(e: std::io::Error) -> BackendError {
+    BackendError(format!("wayland connection failed: {}", e))
+}
+
+/// A connection to the compositor, with the state of the heads it reported.
+struct Connection {
+    // Keeps the connection open.
+    _display: WlDisplay,
+    queue: EventQueue,
+    manager: Main<ZwlrOutputManagerV1>,
+    state: State,
+}
+
+impl Connection {
+    fn new() -> Result<Self, BackendError> {
+        let display = WlDisplay::connect_to_env()
+            .map_err(|e| BackendError(format!("connecting to wayland failed: {}", e)))?;
+        let mut queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let globals = GlobalManager::new(&attached);
+        queue
+            .sync_roundtrip(&mut (), |_, _, _| {})
+            .map_err(io_error)?;
+
+        let manager = globals
+            .instantiate_range::<ZwlrOutputManagerV1>(1, 2)
+            .map_err(|_| {
+                BackendError("the compositor does not support wlr-output-management".into())
+            })?;
+        manager.quick_assign(manager_event);
+
+        let mut conn = Connection {
+            _display: display,
+            queue,
+            manager,
+            state: State::default(),
+        };
+        while conn.state.serial.is_none() {
+            conn.queue
+                .sync_roundtrip(&mut conn.state, |_, _, _| {})
+                .map_err(io_error)?;
+        }
+        conn.state.changed = false;
+
+        Ok(conn)
+    }
+
+    fn displays(&self) -> Vec<Display> {
+        self.state.heads.iter().map(|h| h.display()).collect()
+    }
+
+    /// Describes the wanted state of every head in a new configuration.
+    /// Heads without a change keep their current state.
+    fn configure(&self, changes: &[Change]) -> Main<ZwlrOutputConfigurationV1> {
+        let config = self
+            .manager
+            .create_configuration(self.state.serial.unwrap_or_default());
+        config.quick_assign(configuration_event);
+
+        for head in self.state.heads.iter() {
+            let want = changes
+                .iter()
+                .map(|c| &c.want)
+                .find(|w| w.name == head.name);
+            if !want.map_or(head.enabled, |w| w.active) {
+                config.disable_head(&head.proxy);
+                continue;
+            }
+
+            let ch = config.enable_head(&head.proxy);
+            let want = match want {
+                Some(w) => w,
+                None => continue,
+            };
+
+            match want.modes.iter().find(|m| m.selected) {
+                Some(m) => match head.modes.iter().find(|hm| {
+                    hm.width == m.width && hm.height == m.height && hm.refresh == m.refresh
+                }) {
+                    Some(hm) => ch.set_mode(&hm.proxy),
+                    None => ch.set_custom_mode(m.width, m.height, m.refresh),
+                },
+                // Newly enabled heads need a mode.
+                None if !head.enabled => {
+                    if let Some(hm) = head.modes.iter().find(|m| m.preferred) {
+                        ch.set_mode(&hm.proxy);
+                    }
+                }
+                None => {}
+            }
+            ch.set_position(want.position.0, want.position.1);
+            ch.set_transform(transform_to_wl(want.transform));
+            ch.set_scale(want.scale.0);
+        }
+
+        config
+    }
+
+    /// Waits for the compositor to accept or reject a configuration.
+    fn wait_result(&mut self) -> Result<(), BackendError> {
+        self.state.result = None;
+        loop {
+            self.queue
+                .dispatch(&mut self.state, |_, _, _| {})
+                .map_err(io_error)?;
+            if let Some(result) = self.state.result.take() {
+                return result.map_err(BackendError);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WlrBackend;
+
+impl OutputBackend for WlrBackend {
+    fn outputs(&mut self) -> Result<Vec<Display>, BackendError> {
+        Ok(Connection::new()?.displays())
+    }
+
+    /// Tests the configuration first, and only applies it if the compositor
+    /// accepts it.
+    fn apply(&mut self, changes: &[Change]) -> Result<(), BackendError> {
+        let mut conn = Connection::new()?;
+
+        let test = conn.configure(changes);
+        test.test();
+        let tested = conn.wait_result();
+        test.destroy();
+        tested?;
+
+        let config = conn.configure(changes);
+        config.apply();
+        let applied = conn.wait_result();
+        config.destroy();
+        applied
+    }
+
+    fn subscribe(&self, mut on_change: OutputsCallback) {
+        std::thread::spawn(move || {
+            let mut conn = match Connection::new() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    on_change(Err(e));
+                    return;
+                }
+            };
+
+            loop {
+                if let Err(e) = conn.queue.dispatch(&mut conn.state, |_, _, _| {}) {
+                    on_change(Err(io_error(e)));
+                    return;
+                }
+                if conn.state.changed {
+                    conn.state.changed = false;
+                    if !on_change(Ok(conn.displays())) {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_round_trip() {
+        for t in [
+            Transform::None,
+            Transform::R90,
+            Transform::R180,
+            Transform::R270,
+            Transform::Flipped,
+            Transform::Flipped90,
+            Transform::Flipped180,
+            Transform::Flipped270,
+        ] {
+            assert_eq!(transform_from_wl(transform_to_wl(t)), t);
+        }
+        assert_eq!(
+            transform_to_wl(Transform::Flipped90),
+            WlTransform::Flipped90
+        );
+    }
+}
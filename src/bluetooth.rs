@@ -4,6 +4,8 @@ use druid::{
 };
 
 use crate::model::bluetooth::{commands, App, Device};
+use crate::model::notification::{self, Level};
+use crate::widgets::notifications::build_notifications;
 
 use crate::Opt;
 use druid::widget::prelude::*;
@@ -20,49 +22,78 @@ use futures::executor::block_on;
 use std::sync::mpsc;
 use std::thread;
 
-pub struct BluetoothDelegate<'a> {
+/// The connection to bluetoothd.
+struct Adapter<'a> {
     session: &'static mut BluetoothSession,
     adapter: BluetoothAdapter<'a>,
     bds: BluetoothDiscoverySession<'a>,
-    sink: ExtEventSink,
 }
 
-impl<'a> BluetoothDelegate<'a> {
-    pub fn new(sink: ExtEventSink) -> Self {
-        let adapter = BluetoothAdapter::init(Box::leak(Box::new(
-            BluetoothSession::create_session(None).unwrap(),
-        )))
-        .unwrap();
+impl<'a> Adapter<'a> {
+    fn init(sink: &ExtEventSink) -> Result<Self, Box<dyn std::error::Error>> {
+        let adapter =
+            BluetoothAdapter::init(Box::leak(Box::new(BluetoothSession::create_session(None)?)))?;
 
         sink.submit_command(
             commands::UPDATE_STATUS,
-            format!(
-                "Using: {} ({})",
-                adapter.get_id(),
-                adapter.get_name().unwrap()
-            ),
+            format!("Using: {} ({})", adapter.get_id(), adapter.get_name()?),
             Target::Auto,
-        );
+        )?;
 
-        if !adapter.is_powered().unwrap() {
-            adapter.set_powered(true).unwrap();
+        if !adapter.is_powered()? {
+            adapter.set_powered(true)?;
         }
 
         let bds = BluetoothDiscoverySession::create_session(
-            Box::leak(Box::new(BluetoothSession::create_session(None).unwrap())),
+            Box::leak(Box::new(BluetoothSession::create_session(None)?)),
             adapter.get_id(),
-        )
-        .unwrap();
+        )?;
 
         let session: &'static mut BluetoothSession =
-            Box::leak(Box::new(BluetoothSession::create_session(None).unwrap()));
+            Box::leak(Box::new(BluetoothSession::create_session(None)?));
 
-        BluetoothDelegate {
+        Ok(Adapter {
             session,
             adapter,
             bds,
-            sink,
-        }
+        })
+    }
+
+    fn device(&self, id: String) -> Result<Device, Box<dyn std::error::Error>> {
+        let device = BluetoothDevice::new(self.session, id);
+
+        Ok(Device {
+            id: device.get_id(),
+            addr: device.get_address()?,
+            name: device.get_name().unwrap_or("".into()),
+            rssi: device.get_rssi().ok(),
+            connected: device.is_connected()?,
+        })
+    }
+}
+
+pub struct BluetoothDelegate<'a> {
+    /// None if bluetoothd could not be reached.
+    bt: Option<Adapter<'a>>,
+    sink: ExtEventSink,
+}
+
+impl<'a> BluetoothDelegate<'a> {
+    pub fn new(sink: ExtEventSink) -> Self {
+        let bt = match Adapter::init(&sink) {
+            Ok(bt) => Some(bt),
+            Err(e) => {
+                sink.submit_command(
+                    notification::commands::NOTIFY_ERROR,
+                    format!("Bluetooth is unavailable (is bluetoothd running?): {}", e),
+                    Target::Auto,
+                )
+                .ok();
+                None
+            }
+        };
+
+        BluetoothDelegate { bt, sink }
     }
 }
 
@@ -75,61 +106,81 @@ impl AppDelegate<App> for BluetoothDelegate<'_> {
         data: &mut App,
         _env: &Env,
     ) -> Handled {
+        // worker -> UI
+        if let Some(msg) = cmd.get(commands::UPDATE_STATUS) {
+            data.status_text = msg.clone();
+            return Handled::Yes;
+        }
+        if let Some(msg) = cmd.get(notification::commands::NOTIFY_ERROR) {
+            data.notifications.push(Level::Error, msg.clone());
+            return Handled::Yes;
+        }
+
+        let bt = match self.bt.as_mut() {
+            Some(bt) => bt,
+            None => {
+                // Polling for devices would only repeat the error.
+                if cmd.is(commands::CONNECT_TO_DEVICE)
+                    || cmd.is(commands::DISCONNECT_FROM_DEVICE)
+                    || cmd.is(commands::DO_SCAN)
+                {
+                    data.notifications
+                        .push(Level::Error, "Bluetooth is unavailable".into());
+                    return Handled::Yes;
+                }
+                return Handled::No;
+            }
+        };
+
         // UI -> worker
         if let Some(id) = cmd.get(commands::CONNECT_TO_DEVICE) {
-            let dev = BluetoothDevice::new(self.session, id.clone());
-            match dev.connect(6500) {
-                Ok(()) => {}
-                Err(e) => {
-                    println!("connecting to {} failed: {:?}", id, e)
-                }
+            let dev = BluetoothDevice::new(bt.session, id.clone());
+            if let Err(e) = dev.connect(6500) {
+                data.notifications
+                    .error(&format!("Connecting to {} failed", id), e);
             }
             return Handled::Yes;
         }
         if let Some(id) = cmd.get(commands::DISCONNECT_FROM_DEVICE) {
-            let dev = BluetoothDevice::new(self.session, id.clone());
-            match dev.disconnect() {
-                Ok(()) => {}
-                Err(e) => {
-                    println!("disconnecting from {} failed: {:?}", id, e)
-                }
+            let dev = BluetoothDevice::new(bt.session, id.clone());
+            if let Err(e) = dev.disconnect() {
+                data.notifications
+                    .error(&format!("Disconnecting from {} failed", id), e);
             }
             return Handled::Yes;
         }
         if let Some(want) = cmd.get(commands::DO_SCAN) {
-            println!("want scanning state {}", want);
-            match self.adapter.set_discoverable(*want) {
-                Ok(devices) => {
+            match bt.adapter.set_discoverable(*want) {
+                Ok(()) => {
                     data.scanning = *want;
                 }
-                Err(e) => println!("set_discoverable error: {:?}", e),
+                Err(e) => data.notifications.error("Failed to change scanning", e),
             };
-            if *want {
-                self.bds.start_discovery().unwrap();
+            let res = if *want {
+                bt.bds.start_discovery()
             } else {
-                self.bds.stop_discovery().unwrap();
+                bt.bds.stop_discovery()
+            };
+            if let Err(e) = res {
+                data.notifications.error("Failed to change scanning", e);
             }
             return Handled::Yes;
         }
         if let Some(_) = cmd.get(commands::ENUM_DEVICES) {
-            match self.adapter.get_device_list() {
+            match bt.adapter.get_device_list() {
                 Ok(devices) => {
-                    data.devices = devices
-                        .into_iter()
-                        .map(|d| {
-                            let device = BluetoothDevice::new(self.session, d);
-
-                            Device {
-                                id: device.get_id(),
-                                addr: device.get_address().unwrap(),
-                                name: device.get_name().unwrap_or("".into()),
-                                rssi: device.get_rssi().ok(),
-                                connected: device.is_connected().unwrap(),
-                            }
-                        })
-                        .collect();
+                    let mut listed = Vec::with_capacity(devices.len());
+                    for id in devices {
+                        match bt.device(id.clone()) {
+                            Ok(d) => listed.push(d),
+                            Err(e) => data
+                                .notifications
+                                .error(&format!("Failed to read device {}", id), e),
+                        }
+                    }
+                    data.devices = listed.into_iter().collect();
                 }
-                Err(e) => println!("Enum error: {:?}", e),
+                Err(e) => data.notifications.error("Failed to list devices", e),
             };
 
             data.devices.sort_by(|a, b| match (a.rssi, b.rssi) {
@@ -142,12 +193,6 @@ impl AppDelegate<App> for BluetoothDelegate<'_> {
             return Handled::Yes;
         }
 
-        // worker -> UI
-        if let Some(msg) = cmd.get(commands::UPDATE_STATUS) {
-            data.status_text = msg.clone();
-            return Handled::Yes;
-        }
-
         Handled::No
     }
 }
@@ -299,6 +344,12 @@ pub fn build_ui(args: &Opt) -> impl Widget<App> {
         .with_default_spacer()
         .with_child(build_topbar())
         .with_default_spacer()
+        .with_child(
+            build_notifications()
+                .lens(App::notifications)
+                .padding((12., 0.))
+                .expand_width(),
+        )
         .with_flex_child(
             Flex::column()
                 .must_fill_main_axis(true)
@@ -0,0 +1,164 @@
+//! Command line versions of the display UI, for use from scripts.
+
+use serde::Serialize;
+use std::error::Error;
+
+use crate::backend::{self, OutputBackend};
+use crate::model::display::{App, Display, Mode};
+use crate::{DisplayCmd, Opt};
+
+/// Loads the outputs and config the same way the UI does.
+fn load(args: &Opt, backend: &mut dyn OutputBackend) -> Result<App, Box<dyn Error>> {
+    let mut app: App = backend.outputs()?.into();
+    app.use_identifiers = args.use_identifiers;
+    app.load_config(args.file.clone())?;
+    Ok(app)
+}
+
+pub fn run_display(cmd: &DisplayCmd, args: &Opt) -> Result<(), Box<dyn Error>> {
+    let mut backend = backend::open(args.backend);
+    let backend = &mut *backend;
+
+    match cmd {
+        DisplayCmd::List { json } => {
+            let mut app = load(args, backend)?;
+            app.adopt_live();
+
+            let mut displays: Vec<&Display> = app.display_geo.values().collect();
+            displays.sort_by(|a, b| a.name.cmp(&b.name));
+            if *json {
+                print_json(&displays)?;
+            } else {
+                print_table(&displays);
+            }
+        }
+
+        DisplayCmd::Set {
+            output,
+            scale,
+            pos,
+            mode,
+            transform,
+            enable,
+            disable,
+        } => {
+            let mut app = load(args, backend)?;
+            app.adopt_live();
+
+            let d = app
+                .display_geo
+                .iter_mut()
+                .map(|(_, d)| d)
+                .find(|d| d.matches_name(output))
+                .ok_or_else(|| format!("no output named {:?}", output))?;
+            if let Some(scale) = scale {
+                scale.validate()?;
+                d.scale = *scale;
+            }
+            if let Some(pos) = pos {
+                d.position = *pos;
+            }
+            if let Some(transform) = transform {
+                d.transform = *transform;
+            }
+            if let Some(mode) = mode {
+                if !d.select_mode(mode) {
+                    return Err(format!("{} has no mode {}", output, mode_str(mode)).into());
+                }
+            }
+            if *enable {
+                d.active = true;
+            }
+            if *disable {
+                d.active = false;
+            }
+
+            app.apply_displays(backend)?;
+        }
+
+        DisplayCmd::Apply => {
+            let mut app = load(args, backend)?;
+            app.apply_displays(backend)?;
+        }
+
+        DisplayCmd::Save => {
+            let mut app = load(args, backend)?;
+            app.adopt_live();
+            app.save_config(args.file.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn mode_str(m: &Mode) -> String {
+    if m.refresh > 0 {
+        format!("{}x{}@{:.3}Hz", m.width, m.height, m.refresh as f64 / 1000.)
+    } else {
+        format!("{}x{}", m.width, m.height)
+    }
+}
+
+fn print_table(displays: &[&Display]) {
+    for d in displays {
+        println!(
+            "{}\t{}\t{}\tscale {:.2}\tpos {},{}\t{}{}",
+            d.name,
+            d.identifier(),
+            d.modes
+                .iter()
+                .find(|m| m.selected)
+                .map_or("-".to_string(), mode_str),
+            d.scale.0,
+            d.position.0,
+            d.position.1,
+            d.transform,
+            if d.active { "" } else { "\tdisabled" },
+        );
+    }
+}
+
+/// A display as printed by `display list --json`.
+#[derive(Serialize)]
+struct JsonDisplay<'a> {
+    name: &'a str,
+    make: &'a str,
+    model: &'a str,
+    serial: &'a str,
+    active: bool,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    scale: f64,
+    transform: String,
+    mode: Option<String>,
+    modes: Vec<String>,
+}
+
+impl<'a> From<&'a Display> for JsonDisplay<'a> {
+    fn from(d: &'a Display) -> Self {
+        let (width, height) = d.logical_size();
+        JsonDisplay {
+            name: &d.name,
+            make: &d.make,
+            model: &d.model,
+            serial: &d.serial,
+            active: d.active,
+            x: d.position.0,
+            y: d.position.1,
+            width,
+            height,
+            scale: d.scale.0,
+            transform: d.transform.to_string(),
+            mode: d.modes.iter().find(|m| m.selected).map(mode_str),
+            modes: d.modes.iter().map(mode_str).collect(),
+        }
+    }
+}
+
+fn print_json(displays: &[&Display]) -> Result<(), serde_json::Error> {
+    let displays: Vec<JsonDisplay> = displays.iter().map(|d| JsonDisplay::from(*d)).collect();
+    println!("{}", serde_json::to_string(&displays)?);
+    Ok(())
+}
@@ -1,16 +1,150 @@
-use druid::text::ParseFormatter;
-use druid::{Color, Lens, LensExt, Widget, WidgetExt};
+use druid::im::Vector;
+use druid::text::{Formatter, ParseFormatter, Selection, Validation, ValidationError};
+use druid::{
+    AppDelegate, Color, Command, DelegateCtx, ExtEventSink, Handled, Lens, LensExt, Target, Widget,
+    WidgetExt, WindowDesc, WindowId,
+};
 
+use crate::backend::OutputBackend;
 use crate::lens::FocusedDisplay;
-use crate::model::display::{App, Display, Mode, Pos, Scale, Transform};
+use crate::model::backlight::{self, Kind as BacklightKind};
+use crate::model::display::{
+    commands, App, Background, BackgroundMode, ConfigParseError, Display, MaxRenderTime, Mode,
+    PendingChange, Pos, RenderBitDepth, Scale, ScaleSuggestion, Subpixel, Transform,
+};
+use crate::model::history::{self, commands as history_commands};
+use crate::model::nightlight::{self, NightLight, Schedule, TimeOfDay};
+use crate::model::notification::{self, Level};
+use crate::model::profile::Profile;
+use crate::model::workspace::{OutputList, Workspace};
 use crate::widgets::display::MonitorView;
+use crate::widgets::memo::Memo;
+use crate::widgets::notifications::build_notifications;
+use crate::widgets::thumbnail::{Thumbnail, ThumbnailCache};
 use crate::Opt;
 use druid::widget::prelude::*;
 use druid::widget::{
-    Button, Controller, CrossAxisAlignment, Flex, Label, List, MainAxisAlignment, Painter,
-    RadioGroup, Scroll, SizedBox, Split, TextBox,
+    Button, Checkbox, Controller, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List,
+    MainAxisAlignment, Painter, RadioGroup, Scroll, SizedBox, Slider, Split, TextBox, TextBoxEvent,
+    ValidationDelegate,
 };
 
+/// Watches the backend for output changes, and forwards the new set of
+/// outputs to the UI.
+pub fn watch_outputs(sink: ExtEventSink, backend: &dyn OutputBackend) {
+    backend.subscribe(Box::new(move |outputs| match outputs {
+        Ok(outputs) => sink
+            .submit_command(commands::OUTPUTS_CHANGED, outputs, Target::Auto)
+            .is_ok(),
+        Err(e) => sink
+            .submit_command(
+                notification::commands::NOTIFY_ERROR,
+                format!("Problem watching for display changes: {}", e),
+                Target::Auto,
+            )
+            .is_ok(),
+    }));
+}
+
+pub struct DisplayDelegate {
+    backend: Box<dyn OutputBackend + Send>,
+    /// The number of open windows, counting identify windows.
+    windows: usize,
+}
+
+impl DisplayDelegate {
+    pub fn new(backend: Box<dyn OutputBackend + Send>) -> Self {
+        DisplayDelegate {
+            backend,
+            windows: 0,
+        }
+    }
+}
+
+impl AppDelegate<App> for DisplayDelegate {
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut App,
+        _env: &Env,
+    ) -> Handled {
+        // UI -> backend
+        if cmd.is(commands::APPLY_DISPLAYS) {
+            if let Err(e) = data.apply_displays_with_confirm(&mut *self.backend) {
+                data.notifications.error("Failed to apply the displays", e);
+            }
+            return Handled::Yes;
+        }
+        if let Some(name) = cmd.get(commands::PLACE_IDENTIFY_WINDOW) {
+            let title = format!("{}{}", IDENTIFY_TITLE_PREFIX, name);
+            if let Err(e) = self.backend.place_window(&title, name) {
+                data.notifications
+                    .error("Failed to move the identify window", e);
+            }
+            return Handled::Yes;
+        }
+        if cmd.is(commands::REVERT_DISPLAYS) {
+            if let Err(e) = data.revert_displays(&mut *self.backend) {
+                data.notifications.error("Failed to revert the displays", e);
+            }
+            return Handled::Yes;
+        }
+
+        // worker -> UI
+        if let Some(outputs) = cmd.get(commands::OUTPUTS_CHANGED) {
+            if data.merge_outputs(outputs.clone()) {
+                data.apply_matching_profile(&mut *self.backend);
+            }
+            return Handled::Yes;
+        }
+        if let Some(msg) = cmd.get(notification::commands::NOTIFY_ERROR) {
+            data.notifications.push(Level::Error, msg.clone());
+            return Handled::Yes;
+        }
+
+        Handled::No
+    }
+
+    fn window_added(&mut self, _id: WindowId, _data: &mut App, _env: &Env, _ctx: &mut DelegateCtx) {
+        self.windows += 1;
+    }
+
+    fn window_removed(
+        &mut self,
+        _id: WindowId,
+        data: &mut App,
+        _env: &Env,
+        _ctx: &mut DelegateCtx,
+    ) {
+        self.windows = self.windows.saturating_sub(1);
+        if self.windows > 0 {
+            return;
+        }
+        if let Err(e) = data.on_exit(&mut *self.backend) {
+            data.notifications.error("Failed to revert the displays", e);
+        }
+    }
+}
+
+/// A label for the input of a setting, marked when the focused display has
+/// an edit of it which isn't applied yet.
+fn setting_label(text: &'static str, setting: &'static str) -> impl Widget<App> {
+    Label::new(move |data: &App, _env: &_| {
+        let edited = data
+            .display_geo
+            .values()
+            .find(|d| d.focused)
+            .map_or(false, |d| data.unapplied_settings(d).contains(&setting));
+        if edited {
+            format!("{} *", text)
+        } else {
+            text.to_string()
+        }
+    })
+}
+
 fn build_name_row() -> impl Widget<App> {
     Flex::row()
         .must_fill_main_axis(true)
@@ -63,64 +197,294 @@ fn build_info_row() -> impl Widget<App> {
         )
 }
 
+fn build_identifier_input() -> impl Widget<App> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(
+            SizedBox::new(Label::new("Save by model")).expand_width(),
+            0.3,
+        )
+        .with_default_spacer()
+        .with_flex_child(
+            Checkbox::new("Refer to the display by make, model and serial")
+                .lens(focused_display().then(Display::use_identifier))
+                .expand_width(),
+            0.7,
+        )
+        .with_spacer(2.)
+}
+
+fn build_enabled_input() -> impl Widget<App> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(
+            SizedBox::new(setting_label("Enabled", "enabled")).expand_width(),
+            0.3,
+        )
+        .with_default_spacer()
+        .with_flex_child(
+            Checkbox::new("")
+                .lens(focused_display().then(Display::active))
+                .expand_width(),
+            0.7,
+        )
+        .with_spacer(2.)
+}
+
 fn build_pos_input() -> impl Widget<App> {
     Flex::row()
         .must_fill_main_axis(true)
         .main_axis_alignment(MainAxisAlignment::Start)
         .cross_axis_alignment(CrossAxisAlignment::Center)
         .with_default_spacer()
-        .with_flex_child(SizedBox::new(Label::new("Position")).expand_width(), 0.3)
+        .with_flex_child(
+            SizedBox::new(setting_label("Position", "position")).expand_width(),
+            0.3,
+        )
         .with_default_spacer()
         .with_flex_child(
             TextBox::new()
                 .with_formatter(ParseFormatter::<Pos>::new())
                 .update_data_while_editing(true)
-                .lens(
-                    FocusedDisplay
-                        .map(
-                            |x| x.as_ref().unwrap_or(&Display::default()).clone(),
-                            |x, y| {
-                                if x.is_some() {
-                                    *x = Some(y)
-                                }
-                            },
-                        )
-                        .then(Display::position),
-                )
+                .lens(focused_display().then(Display::position))
                 .expand_width(),
             0.7,
         )
         .with_spacer(2.)
 }
 
+/// Edits scales, only letting through characters which can make up one.
+struct ScaleFormatter;
+
+impl Formatter<Scale> for ScaleFormatter {
+    fn format(&self, value: &Scale) -> String {
+        value.to_string()
+    }
+
+    fn validate_partial_input(&self, input: &str, _sel: &Selection) -> Validation {
+        if input.chars().all(|c| c.is_ascii_digit() || c == '.') && input.matches('.').count() <= 1
+        {
+            Validation::success()
+        } else {
+            Validation::failure(ConfigParseError(format!("invalid scale {:?}", input)))
+        }
+    }
+
+    fn value(&self, input: &str) -> Result<Scale, ValidationError> {
+        parse_scale(input).map_err(ValidationError::new)
+    }
+}
+
+/// Parses a scale typed in, rejecting ones out of range.
+fn parse_scale(input: &str) -> Result<Scale, ConfigParseError> {
+    let scale: Scale = input.parse()?;
+    scale.validate()?;
+    Ok(scale)
+}
+
+const SCALE_ERROR_ACTION: druid::Selector<String> = druid::Selector::new("scale_error_action");
+const SCALE_SELECTED_ACTION: druid::Selector<Scale> = druid::Selector::new("scale_selected_action");
+
+/// Reports why the scale being entered is rejected, as it is typed.
+struct ScaleValidation;
+
+impl ValidationDelegate for ScaleValidation {
+    fn event(&mut self, ctx: &mut EventCtx, event: TextBoxEvent, current_text: &str) {
+        let error = match event {
+            TextBoxEvent::Changed => match parse_scale(current_text) {
+                Ok(_) => String::new(),
+                Err(e) => e.to_string(),
+            },
+            TextBoxEvent::PartiallyInvalid(e) | TextBoxEvent::Invalid(e) => e.to_string(),
+            TextBoxEvent::Began | TextBoxEvent::Complete | TextBoxEvent::Cancel => String::new(),
+        };
+        ctx.submit_command(SCALE_ERROR_ACTION.with(error));
+    }
+}
+
+struct ScaleController;
+
+impl<W: Widget<App>> Controller<App, W> for ScaleController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(error) = c.get(SCALE_ERROR_ACTION) {
+                data.scale_error = error.clone();
+                ctx.set_handled();
+            }
+            if let Some(scale) = c.get(SCALE_SELECTED_ACTION) {
+                if let Some((_, d)) = data.display_geo.iter_mut().find(|(_, d)| d.focused) {
+                    d.scale = *scale;
+                }
+                data.scale_error.clear();
+                data.show_scale_suggestions = false;
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+/// Scales at which the focused display has a whole number logical size.
+fn build_scale_suggestions() -> impl Widget<App> {
+    Scroll::new(
+        List::new(|| {
+            Button::new(|s: &ScaleSuggestion, _env: &_| {
+                format!("{} ({}x{})", s.scale, s.size.0, s.size.1)
+            })
+            .on_click(|ctx, s: &mut ScaleSuggestion, _env| {
+                ctx.submit_command(SCALE_SELECTED_ACTION.with(s.scale))
+            })
+            .padding((0., 0., 4., 0.))
+        })
+        .horizontal(),
+    )
+    .horizontal()
+    .lens(druid::lens::Map::new(
+        |data: &App| {
+            data.display_geo
+                .values()
+                .find(|d| d.focused)
+                .map(Display::scale_suggestions)
+                .unwrap_or_default()
+        },
+        |_: &mut App, _: Vector<ScaleSuggestion>| {},
+    ))
+}
+
 fn build_scale_input() -> impl Widget<App> {
     Flex::row()
         .must_fill_main_axis(true)
         .main_axis_alignment(MainAxisAlignment::Start)
         .cross_axis_alignment(CrossAxisAlignment::Center)
         .with_default_spacer()
-        .with_flex_child(Label::new("Scale").expand_width(), 0.3)
+        .with_flex_child(setting_label("Scale", "scale").expand_width(), 0.3)
         .with_default_spacer()
         .with_flex_child(
-            TextBox::new()
-                .with_formatter(ParseFormatter::<Scale>::new())
-                .update_data_while_editing(true)
-                .lens(
-                    FocusedDisplay
-                        .map(
-                            |x| x.as_ref().unwrap_or(&Display::default()).clone(),
-                            |x, y| {
-                                if x.is_some() {
-                                    *x = Some(y)
-                                }
-                            },
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Flex::row()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_formatter(ScaleFormatter)
+                                .update_data_while_editing(true)
+                                .delegate(ScaleValidation)
+                                .lens(focused_display().then(Display::scale))
+                                .expand_width(),
+                            1.,
                         )
-                        .then(Display::scale),
+                        .with_default_spacer()
+                        .with_child(Button::new("Suggest").on_click(
+                            |_ctx, data: &mut App, _env| {
+                                data.show_scale_suggestions = !data.show_scale_suggestions
+                            },
+                        )),
                 )
-                .expand_width(),
+                .with_child(
+                    Label::new(|data: &App, _env: &_| data.scale_error.clone())
+                        .with_text_color(Color::rgb8(230, 90, 90)),
+                )
+                .with_child(Either::new(
+                    |data: &App, _env| data.show_scale_suggestions,
+                    build_scale_suggestions(),
+                    SizedBox::empty(),
+                )),
             0.7,
         )
         .with_spacer(2.)
+        .controller(ScaleController)
+}
+
+/// Sets the brightness of backlights as their sliders are moved.
+#[derive(Default)]
+struct BacklightController {
+    worker: Option<backlight::Worker>,
+}
+
+impl<W: Widget<App>> Controller<App, W> for BacklightController {
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &App,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            let sink = ctx.get_external_handle();
+            self.worker = Some(backlight::Worker::new(move |name, e| {
+                let _ = sink.submit_command(
+                    notification::commands::NOTIFY_ERROR,
+                    format!("Failed to set the brightness of {}: {}", name, e),
+                    Target::Auto,
+                );
+            }));
+        }
+
+        child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old: &App, data: &App, env: &Env) {
+        if let Some(worker) = &self.worker {
+            for (old_b, b) in old.backlights.iter().zip(data.backlights.iter()) {
+                if !old_b.level.same(&b.level) {
+                    worker.set(b);
+                }
+            }
+        }
+
+        child.update(ctx, old, data, env)
+    }
+}
+
+/// Lens onto the level of the focused display's backlight of a kind.
+fn focused_backlight(kind: BacklightKind) -> impl Lens<App, f64> {
+    druid::lens::Map::new(
+        move |data: &App| {
+            data.focused_backlight(kind)
+                .map_or(0., |i| data.backlights[i].level)
+        },
+        move |data: &mut App, level| {
+            if let Some(i) = data.focused_backlight(kind) {
+                data.backlights[i].level = level;
+            }
+        },
+    )
+}
+
+fn build_brightness_row(label: &str, kind: BacklightKind) -> impl Widget<App> {
+    Either::new(
+        move |data: &App, _env: &_| data.focused_backlight(kind).is_some(),
+        build_advanced_row(
+            label,
+            Slider::new().lens(focused_backlight(kind)).expand_width(),
+        ),
+        SizedBox::empty(),
+    )
+}
+
+fn build_brightness_input() -> impl Widget<App> {
+    Flex::column()
+        .with_child(build_brightness_row("Brightness", BacklightKind::Screen))
+        .with_child(build_brightness_row(
+            "Keyboard light",
+            BacklightKind::Keyboard,
+        ))
+        .controller(BacklightController::default())
 }
 
 fn build_rotation_input() -> impl Widget<App> {
@@ -129,7 +493,10 @@ fn build_rotation_input() -> impl Widget<App> {
         .main_axis_alignment(MainAxisAlignment::Start)
         .cross_axis_alignment(CrossAxisAlignment::Center)
         .with_default_spacer()
-        .with_flex_child(SizedBox::new(Label::new("Rotation")).expand_width(), 0.3)
+        .with_flex_child(
+            SizedBox::new(setting_label("Rotation", "transform")).expand_width(),
+            0.3,
+        )
         .with_default_spacer()
         .with_flex_child(
             Scroll::new(
@@ -138,19 +505,12 @@ fn build_rotation_input() -> impl Widget<App> {
                     ("90", Transform::R90),
                     ("180", Transform::R180),
                     ("270", Transform::R270),
+                    ("Flipped", Transform::Flipped),
+                    ("Flipped 90", Transform::Flipped90),
+                    ("Flipped 180", Transform::Flipped180),
+                    ("Flipped 270", Transform::Flipped270),
                 ])
-                .lens(
-                    FocusedDisplay
-                        .map(
-                            |x| x.as_ref().unwrap_or(&Display::default()).clone(),
-                            |x, y| {
-                                if x.is_some() {
-                                    *x = Some(y)
-                                }
-                            },
-                        )
-                        .then(Display::transform),
-                )
+                .lens(focused_display().then(Display::transform))
                 .expand_width(),
             )
             .horizontal(),
@@ -158,11 +518,101 @@ fn build_rotation_input() -> impl Widget<App> {
         )
 }
 
-const MODE_SELECTED_ACTION: druid::Selector<Mode> = druid::Selector::new("mode_selected_action");
+/// Lens onto the focused display, falling back to a default display when
+/// nothing is focused.
+fn focused_display() -> impl Lens<App, Display> {
+    FocusedDisplay.map(
+        |x| x.as_ref().unwrap_or(&Display::default()).clone(),
+        |x, y| {
+            if x.is_some() {
+                *x = Some(y)
+            }
+        },
+    )
+}
 
-struct SingleModeController;
+fn build_advanced_row<W: Widget<App> + 'static>(label: &str, input: W) -> impl Widget<App> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(SizedBox::new(Label::new(label)).expand_width(), 0.3)
+        .with_default_spacer()
+        .with_flex_child(input, 0.7)
+        .with_spacer(2.)
+}
 
-impl<W: Widget<App>> Controller<App, W> for SingleModeController {
+fn build_advanced_input() -> impl Widget<App> {
+    Flex::column()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_default_spacer()
+                .with_child(Checkbox::new("Advanced").lens(App::show_advanced)),
+        )
+        .with_child(Either::new(
+            |data: &App, _env: &_| data.show_advanced,
+            Flex::column()
+                .must_fill_main_axis(true)
+                .with_spacer(2.)
+                .with_child(build_advanced_row(
+                    "Adaptive sync",
+                    Checkbox::new("")
+                        .lens(focused_display().then(Display::adaptive_sync))
+                        .expand_width(),
+                ))
+                .with_child(build_advanced_row(
+                    "Allow tearing",
+                    Checkbox::new("")
+                        .lens(focused_display().then(Display::allow_tearing))
+                        .expand_width(),
+                ))
+                .with_child(build_advanced_row(
+                    "Subpixel",
+                    Scroll::new(
+                        RadioGroup::row(vec![
+                            ("Unknown", Subpixel::Unknown),
+                            ("None", Subpixel::None),
+                            ("RGB", Subpixel::Rgb),
+                            ("BGR", Subpixel::Bgr),
+                            ("VRGB", Subpixel::Vrgb),
+                            ("VBGR", Subpixel::Vbgr),
+                        ])
+                        .lens(focused_display().then(Display::subpixel))
+                        .expand_width(),
+                    )
+                    .horizontal(),
+                ))
+                .with_child(build_advanced_row(
+                    "Render bit depth",
+                    RadioGroup::row(vec![
+                        ("6", RenderBitDepth::Six),
+                        ("8", RenderBitDepth::Eight),
+                        ("10", RenderBitDepth::Ten),
+                    ])
+                    .lens(focused_display().then(Display::render_bit_depth))
+                    .expand_width(),
+                ))
+                .with_child(build_advanced_row(
+                    "Max render time (ms)",
+                    TextBox::new()
+                        .with_formatter(ParseFormatter::<MaxRenderTime>::new())
+                        .update_data_while_editing(true)
+                        .lens(focused_display().then(Display::max_render_time))
+                        .expand_width(),
+                )),
+            SizedBox::empty(),
+        ))
+}
+
+const WALLPAPER_SELECTED_ACTION: druid::Selector<String> =
+    druid::Selector::new("wallpaper_selected_action");
+
+struct WallpaperController;
+
+impl<W: Widget<App>> Controller<App, W> for WallpaperController {
     fn event(
         &mut self,
         child: &mut W,
@@ -172,13 +622,12 @@ impl<W: Widget<App>> Controller<App, W> for SingleModeController {
         env: &Env,
     ) {
         if let Event::Command(c) = event {
-            if let Some(nm) = c.get(MODE_SELECTED_ACTION) {
+            if let Some(path) = c.get(WALLPAPER_SELECTED_ACTION) {
                 for (_, d) in data.display_geo.iter_mut() {
                     if d.focused {
-                        for m in d.modes.iter_mut() {
-                            m.selected = m.width == nm.width
-                                && m.height == nm.height
-                                && m.refresh == nm.refresh;
+                        d.background.source = path.clone();
+                        if d.background.mode == BackgroundMode::SolidColor {
+                            d.background.mode = BackgroundMode::Fill;
                         }
                     }
                 }
@@ -190,124 +639,1108 @@ impl<W: Widget<App>> Controller<App, W> for SingleModeController {
     }
 }
 
-fn build_mode_input() -> impl Widget<App> {
-    Flex::row()
-        .must_fill_main_axis(true)
-        .main_axis_alignment(MainAxisAlignment::Start)
-        .cross_axis_alignment(CrossAxisAlignment::Center)
-        .with_default_spacer()
-        .with_flex_child(SizedBox::new(Label::new("Mode")).expand_width(), 0.3)
-        .with_default_spacer()
-        .with_flex_child(
-            Scroll::new(
-                List::new(|| {
-                    let painter = Painter::new(|ctx, data: &Mode, env| {
-                        let bounds = ctx.size().to_rect();
-                        ctx.fill(
-                            bounds,
-                            &env.get(if data.selected {
-                                druid::theme::PRIMARY_DARK
-                            } else {
-                                druid::theme::WINDOW_BACKGROUND_COLOR
-                            }),
-                        );
-                    });
+fn build_thumbnail(cache: ThumbnailCache) -> impl Widget<String> {
+    Thumbnail::new(cache)
+        .fix_size(96., 54.)
+        .border(Color::rgb8(80, 80, 80), 1.)
+}
 
-                    Label::new(|item: &Mode, _env: &_| {
-                        format!(
-                            "{}x{}, {:.2} Hz",
-                            item.width,
-                            item.height,
-                            item.refresh as f64 / 1000.
+fn build_background_input() -> impl Widget<App> {
+    let background = || focused_display().then(Display::background);
+    let thumbnails = ThumbnailCache::default();
+
+    Flex::column()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_default_spacer()
+                .with_child(Checkbox::new("Background").lens(App::show_background)),
+        )
+        .with_child(Either::new(
+            |data: &App, _env: &_| data.show_background,
+            Flex::column()
+                .must_fill_main_axis(true)
+                .with_spacer(2.)
+                .with_child(build_advanced_row(
+                    "Fit",
+                    Scroll::new(
+                        RadioGroup::row(vec![
+                            ("Fill", BackgroundMode::Fill),
+                            ("Fit", BackgroundMode::Fit),
+                            ("Stretch", BackgroundMode::Stretch),
+                            ("Center", BackgroundMode::Center),
+                            ("Tile", BackgroundMode::Tile),
+                            ("Solid colour", BackgroundMode::SolidColor),
+                        ])
+                        .lens(background().then(Background::mode))
+                        .expand_width(),
+                    )
+                    .horizontal(),
+                ))
+                .with_child(build_advanced_row(
+                    "Image or colour",
+                    Flex::row()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_placeholder("/path/to/image or #rrggbb")
+                                .lens(background().then(Background::source))
+                                .expand_width(),
+                            1.,
                         )
-                    })
-                    .align_vertical(druid::UnitPoint::LEFT)
-                    .background(painter)
-                    .on_click(move |ctx, data: &mut Mode, _env| {
-                        ctx.submit_command(druid::Command::new(
-                            MODE_SELECTED_ACTION,
-                            data.clone(),
-                            druid::Target::Auto,
-                        ))
-                    })
-                    .expand_width()
-                })
-                .lens(
-                    FocusedDisplay
-                        .map(
-                            |x| x.as_ref().unwrap_or(&Display::default()).clone(),
-                            |x, y| {
-                                if x.is_some() {
-                                    *x = Some(y)
+                        .with_default_spacer()
+                        .with_child(
+                            Painter::new(|ctx, bg: &Background, _env| {
+                                if let (BackgroundMode::SolidColor, Some((r, g, b))) =
+                                    (bg.mode, bg.rgb())
+                                {
+                                    let bounds = ctx.size().to_rect();
+                                    ctx.fill(bounds, &Color::rgb8(r, g, b));
                                 }
-                            },
-                        )
-                        .then(Display::modes),
-                )
-                .controller(SingleModeController {}),
-            )
-            .vertical()
-            .expand_height(),
-            0.7,
-        )
+                            })
+                            .fix_size(20., 20.)
+                            .lens(background()),
+                        ),
+                ))
+                .with_child(build_advanced_row(
+                    "Rotate every (min)",
+                    TextBox::new()
+                        .with_formatter(ParseFormatter::<u32>::new())
+                        .update_data_while_editing(true)
+                        .lens(background().then(Background::rotate_minutes))
+                        .expand_width(),
+                ))
+                .with_child(build_advanced_row(
+                    "Library",
+                    Scroll::new(
+                        List::new(move || {
+                            build_thumbnail(thumbnails.clone()).on_click(
+                                |ctx, data: &mut String, _env| {
+                                    ctx.submit_command(druid::Command::new(
+                                        WALLPAPER_SELECTED_ACTION,
+                                        data.clone(),
+                                        druid::Target::Auto,
+                                    ))
+                                },
+                            )
+                        })
+                        .horizontal()
+                        .with_spacing(4.)
+                        .lens(App::wallpapers),
+                    )
+                    .horizontal()
+                    .controller(WallpaperController {}),
+                )),
+            SizedBox::empty(),
+        ))
 }
 
-fn build_buttons(args: &Opt) -> impl Widget<App> {
-    let base_path = args.file.clone();
+const WORKSPACE_OUTPUT_ACTION: druid::Selector<String> =
+    druid::Selector::new("workspace_output_action");
 
-    SizedBox::new(
-        Flex::row()
-            .must_fill_main_axis(true)
-            .main_axis_alignment(MainAxisAlignment::End)
-            .cross_axis_alignment(CrossAxisAlignment::Center)
-            .with_default_spacer()
-            .with_flex_child(
-                Button::new("Apply now")
-                    .on_click(|_ctx, data: &mut App, _env| data.apply_displays()),
-                0.3,
-            )
-            .with_default_spacer()
-            .with_flex_child(
-                Button::new("Save config").on_click(move |_ctx, data: &mut App, _env| {
-                    data.save_config(base_path.clone()).unwrap()
-                }),
-                0.3,
-            )
-            .with_default_spacer(),
-    )
-    .expand_width()
-}
+struct WorkspaceController;
 
-pub fn build_ui(args: &Opt) -> impl Widget<App> {
-    Split::rows(
-        MonitorView::default().lens(App::display_geo),
-        Flex::column()
-            .must_fill_main_axis(true)
-            .main_axis_alignment(MainAxisAlignment::SpaceBetween)
-            .cross_axis_alignment(CrossAxisAlignment::Center)
-            .with_flex_child(
+impl<W: Widget<App>> Controller<App, W> for WorkspaceController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(name) = c.get(WORKSPACE_OUTPUT_ACTION) {
+                data.add_workspace_output(name);
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_workspace_row() -> impl Widget<Workspace> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_child(Label::new(|w: &Workspace, _env: &_| w.name.clone()).fix_width(60.))
+        .with_flex_child(
+            TextBox::new()
+                .with_placeholder("Any output")
+                .with_formatter(ParseFormatter::<OutputList>::new())
+                .lens(Workspace::outputs)
+                .expand_width(),
+            1.,
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new("Add selected").on_click(|ctx, data: &mut Workspace, _env| {
+                ctx.submit_command(druid::Command::new(
+                    WORKSPACE_OUTPUT_ACTION,
+                    data.name.clone(),
+                    druid::Target::Auto,
+                ))
+            }),
+        )
+        .with_spacer(2.)
+        .with_child(
+            Button::new("Clear").on_click(|_ctx, data: &mut Workspace, _env| {
+                data.outputs = OutputList::default();
+            }),
+        )
+}
+
+/// Rules placing each workspace on the first connected output of a list.
+fn build_workspace_input() -> impl Widget<App> {
+    Flex::column()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_default_spacer()
+                .with_child(Checkbox::new("Workspaces").lens(App::show_workspaces)),
+        )
+        .with_child(Either::new(
+            |data: &App, _env: &_| data.show_workspaces,
+            List::new(build_workspace_row)
+                .with_spacing(2.)
+                .lens(App::workspaces)
+                .controller(WorkspaceController {})
+                .padding((8., 2., 8., 0.)),
+            SizedBox::empty(),
+        ))
+}
+
+/// Sent once the night light settings are saved, to restart wlsunset with
+/// them.
+const RESTART_NIGHT_LIGHT_ACTION: druid::Selector<()> =
+    druid::Selector::new("restart_night_light_action");
+
+/// Carries out the night light settings: previews the night temperature
+/// while asked to, and restarts wlsunset once they are saved. The preview
+/// ends when the controller is dropped.
+#[derive(Default)]
+struct NightLightController {
+    worker: Option<nightlight::Worker>,
+}
+
+impl NightLightController {
+    fn run(&self, job: nightlight::Job) {
+        if let Some(worker) = &self.worker {
+            worker.run(job);
+        }
+    }
+}
+
+impl<W: Widget<App>> Controller<App, W> for NightLightController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if c.is(RESTART_NIGHT_LIGHT_ACTION) {
+                self.run(nightlight::Job::Restart(data.night_light.clone()));
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &App,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            let sink = ctx.get_external_handle();
+            self.worker = Some(nightlight::Worker::new(move |what, e| {
+                let _ = sink.submit_command(
+                    notification::commands::NOTIFY_ERROR,
+                    format!("{}: {}", what, e),
+                    Target::Auto,
+                );
+            }));
+        }
+
+        child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old: &App, data: &App, env: &Env) {
+        let (old_nl, nl) = (&old.night_light, &data.night_light);
+        if old_nl.preview != nl.preview || (nl.preview && old_nl.low_temp != nl.low_temp) {
+            self.run(nightlight::Job::Preview(nl.clone()));
+        }
+
+        child.update(ctx, old, data, env)
+    }
+}
+
+fn build_night_light_input() -> impl Widget<App> {
+    let night_light = || App::night_light;
+
+    Flex::column()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_default_spacer()
+                .with_child(Checkbox::new("Night light").lens(App::show_night_light)),
+        )
+        .with_child(Either::new(
+            |data: &App, _env: &_| data.show_night_light,
+            Flex::column()
+                .must_fill_main_axis(true)
+                .with_spacer(2.)
+                .with_child(build_advanced_row(
+                    "Enabled",
+                    Flex::row()
+                        .with_child(
+                            Checkbox::new("Run wlsunset")
+                                .lens(night_light().then(NightLight::enabled)),
+                        )
+                        .with_default_spacer()
+                        .with_child(
+                            Checkbox::new("Preview night")
+                                .lens(night_light().then(NightLight::preview)),
+                        ),
+                ))
+                .with_child(build_advanced_row(
+                    "Night / day (K)",
+                    Flex::row()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_formatter(ParseFormatter::<u32>::new())
+                                .lens(night_light().then(NightLight::low_temp))
+                                .expand_width(),
+                            1.,
+                        )
+                        .with_default_spacer()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_formatter(ParseFormatter::<u32>::new())
+                                .lens(night_light().then(NightLight::high_temp))
+                                .expand_width(),
+                            1.,
+                        ),
+                ))
+                .with_child(build_advanced_row(
+                    "Schedule",
+                    RadioGroup::row(vec![
+                        ("Fixed times", Schedule::Fixed),
+                        ("Location", Schedule::Location),
+                    ])
+                    .lens(night_light().then(NightLight::schedule)),
+                ))
+                .with_child(Either::new(
+                    |data: &App, _env: &_| data.night_light.schedule == Schedule::Fixed,
+                    build_advanced_row(
+                        "Sunset / sunrise",
+                        Flex::row()
+                            .with_flex_child(
+                                TextBox::new()
+                                    .with_formatter(ParseFormatter::<TimeOfDay>::new())
+                                    .lens(night_light().then(NightLight::sunset))
+                                    .expand_width(),
+                                1.,
+                            )
+                            .with_default_spacer()
+                            .with_flex_child(
+                                TextBox::new()
+                                    .with_formatter(ParseFormatter::<TimeOfDay>::new())
+                                    .lens(night_light().then(NightLight::sunrise))
+                                    .expand_width(),
+                                1.,
+                            ),
+                    ),
+                    build_advanced_row(
+                        "Latitude / longitude",
+                        Flex::row()
+                            .with_flex_child(
+                                TextBox::new()
+                                    .with_formatter(ParseFormatter::<f64>::new())
+                                    .lens(night_light().then(NightLight::latitude))
+                                    .expand_width(),
+                                1.,
+                            )
+                            .with_default_spacer()
+                            .with_flex_child(
+                                TextBox::new()
+                                    .with_formatter(ParseFormatter::<f64>::new())
+                                    .lens(night_light().then(NightLight::longitude))
+                                    .expand_width(),
+                                1.,
+                            ),
+                    ),
+                ))
+                .with_child(build_advanced_row(
+                    "Outputs",
+                    TextBox::new()
+                        .with_placeholder("All outputs")
+                        .with_formatter(ParseFormatter::<OutputList>::new())
+                        .lens(night_light().then(NightLight::outputs))
+                        .expand_width(),
+                )),
+            SizedBox::empty(),
+        ))
+        .controller(NightLightController::default())
+}
+
+const RESET_DISPLAY_ACTION: druid::Selector<String> = druid::Selector::new("reset_display_action");
+
+struct ResetController;
+
+impl<W: Widget<App>> Controller<App, W> for ResetController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(name) = c.get(RESET_DISPLAY_ACTION) {
+                data.reset_display(name);
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_code_label(text: fn(&PendingChange) -> String) -> impl Widget<PendingChange> {
+    Label::new(move |c: &PendingChange, _env: &_| text(c))
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE))
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .expand_width()
+}
+
+fn build_pending_change() -> impl Widget<PendingChange> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new(|c: &PendingChange, _env: &_| c.name.clone()))
+                .with_default_spacer()
+                .with_child(Button::new("Reset").on_click(
+                    |ctx, data: &mut PendingChange, _env| {
+                        ctx.submit_command(RESET_DISPLAY_ACTION.with(data.name.clone()))
+                    },
+                )),
+        )
+        .with_child(Either::new(
+            |c: &PendingChange, _env: &_| c.command.is_empty(),
+            Label::new("Applied"),
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Label::new("Apply runs:"))
+                .with_child(build_code_label(|c| c.command.clone())),
+        ))
+        .with_child(Either::new(
+            |c: &PendingChange, _env: &_| c.config.is_empty(),
+            Label::new("Saved"),
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Label::new("Save writes:"))
+                .with_child(build_code_label(|c| c.config.clone())),
+        ))
+}
+
+/// What Apply and Save would do, with a way to drop the unapplied edits of
+/// each display.
+fn build_review_input() -> impl Widget<App> {
+    Flex::column()
+        .must_fill_main_axis(true)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_default_spacer()
+                .with_child(Checkbox::new("Review changes").lens(App::show_review)),
+        )
+        .with_child(Either::new(
+            |data: &App, _env: &_| data.show_review,
+            // Read only: the list is worked out from the displays.
+            Memo::read_only(
+                Either::new(
+                    |pending: &Vector<PendingChange>, _env: &_| pending.is_empty(),
+                    Label::new("No changes"),
+                    List::new(build_pending_change).with_spacing(6.),
+                ),
+                App::pending_changes,
+            )
+            .controller(ResetController {})
+            .padding((8., 2., 8., 0.)),
+            SizedBox::empty(),
+        ))
+}
+
+const MODE_SELECTED_ACTION: druid::Selector<Mode> = druid::Selector::new("mode_selected_action");
+
+struct SingleModeController;
+
+impl<W: Widget<App>> Controller<App, W> for SingleModeController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(nm) = c.get(MODE_SELECTED_ACTION) {
+                for (_, d) in data.display_geo.iter_mut() {
+                    if d.focused {
+                        for m in d.modes.iter_mut() {
+                            m.selected = m.same_mode(nm) && m.custom == nm.custom;
+                        }
+                    }
+                }
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_mode_input() -> impl Widget<App> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(
+            SizedBox::new(setting_label("Mode", "mode")).expand_width(),
+            0.3,
+        )
+        .with_default_spacer()
+        .with_flex_child(
+            Scroll::new(
+                List::new(|| {
+                    let painter = Painter::new(|ctx, data: &Mode, env| {
+                        let bounds = ctx.size().to_rect();
+                        ctx.fill(
+                            bounds,
+                            &env.get(if data.selected {
+                                druid::theme::PRIMARY_DARK
+                            } else {
+                                druid::theme::WINDOW_BACKGROUND_COLOR
+                            }),
+                        );
+                    });
+
+                    Label::new(|item: &Mode, _env: &_| {
+                        format!(
+                            "{}x{}, {:.2} Hz{}",
+                            item.width,
+                            item.height,
+                            item.refresh as f64 / 1000.,
+                            match (&item.modeline, item.custom) {
+                                (Some(_), _) => " (modeline)",
+                                (None, true) => " (custom)",
+                                (None, false) => "",
+                            }
+                        )
+                    })
+                    .align_vertical(druid::UnitPoint::LEFT)
+                    .background(painter)
+                    .on_click(move |ctx, data: &mut Mode, _env| {
+                        ctx.submit_command(druid::Command::new(
+                            MODE_SELECTED_ACTION,
+                            data.clone(),
+                            druid::Target::Auto,
+                        ))
+                    })
+                    .expand_width()
+                })
+                .lens(focused_display().then(Display::modes))
+                .controller(SingleModeController {}),
+            )
+            .vertical()
+            .expand_height(),
+            0.7,
+        )
+}
+
+const PROFILE_SELECTED_ACTION: druid::Selector<String> =
+    druid::Selector::new("profile_selected_action");
+
+struct ProfileController;
+
+impl<W: Widget<App>> Controller<App, W> for ProfileController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(name) = c.get(PROFILE_SELECTED_ACTION) {
+                data.apply_profile(name);
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_profile_row(args: &Opt) -> impl Widget<App> {
+    let base_path = args.file.clone();
+
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(SizedBox::new(Label::new("Profiles")).expand_width(), 0.3)
+        .with_default_spacer()
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Scroll::new(
+                        List::new(|| {
+                            Button::new(|p: &Profile, _env: &_| p.name.clone()).on_click(
+                                |ctx, data: &mut Profile, _env| {
+                                    ctx.submit_command(druid::Command::new(
+                                        PROFILE_SELECTED_ACTION,
+                                        data.name.clone(),
+                                        druid::Target::Auto,
+                                    ))
+                                },
+                            )
+                        })
+                        .horizontal()
+                        .with_spacing(4.)
+                        .lens(App::profiles),
+                    )
+                    .horizontal(),
+                )
+                .with_spacer(2.)
+                .with_child(
+                    Flex::row()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_placeholder("Profile name")
+                                .lens(App::profile_name)
+                                .expand_width(),
+                            1.,
+                        )
+                        .with_default_spacer()
+                        .with_child(Button::new("Save as profile").on_click(
+                            move |_ctx, data: &mut App, _env| {
+                                if let Err(e) = data.save_profile(base_path.clone()) {
+                                    data.notifications.error("Failed to save the profile", e);
+                                }
+                            },
+                        )),
+                )
+                .controller(ProfileController {}),
+            0.7,
+        )
+}
+
+struct RevertController {
+    timer: druid::TimerToken,
+}
+
+impl Default for RevertController {
+    fn default() -> Self {
+        Self {
+            timer: druid::TimerToken::INVALID,
+        }
+    }
+}
+
+impl<W: Widget<App>> Controller<App, W> for RevertController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        if let Event::Timer(id) = event {
+            if *id == self.timer {
+                if let Some(revert) = data.pending_revert.as_mut() {
+                    revert.seconds_left = revert.seconds_left.saturating_sub(1);
+                    if revert.seconds_left == 0 {
+                        ctx.submit_command(commands::REVERT_DISPLAYS);
+                    } else {
+                        self.timer = ctx.request_timer(std::time::Duration::from_secs(1));
+                    }
+                }
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old: &App, data: &App, env: &Env) {
+        if old.pending_revert.is_none() && data.pending_revert.is_some() {
+            self.timer = ctx.request_timer(std::time::Duration::from_secs(1));
+        }
+
+        child.update(ctx, old, data, env)
+    }
+}
+
+fn build_revert_banner() -> impl Widget<App> {
+    Either::new(
+        |data: &App, _env: &_| data.pending_revert.is_some(),
+        Flex::row()
+            .must_fill_main_axis(true)
+            .main_axis_alignment(MainAxisAlignment::Start)
+            .cross_axis_alignment(CrossAxisAlignment::Center)
+            .with_default_spacer()
+            .with_flex_child(
+                Label::new(|data: &App, _env: &_| {
+                    format!(
+                        "Keep these display settings? Reverting in {}s.",
+                        data.pending_revert.as_ref().map_or(0, |r| r.seconds_left)
+                    )
+                })
+                .expand_width(),
+                0.6,
+            )
+            .with_default_spacer()
+            .with_child(
+                Button::new("Keep changes")
+                    .on_click(|_ctx, data: &mut App, _env| data.keep_displays()),
+            )
+            .with_default_spacer()
+            .with_child(
+                Button::new("Revert").on_click(|ctx, _data: &mut App, _env| {
+                    ctx.submit_command(commands::REVERT_DISPLAYS)
+                }),
+            )
+            .with_default_spacer()
+            .padding(4.)
+            .background(Color::rgb8(90, 70, 20)),
+        SizedBox::empty(),
+    )
+    .controller(RevertController::default())
+}
+
+fn build_custom_mode_input() -> impl Widget<App> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_default_spacer()
+        .with_flex_child(SizedBox::new(Label::new("Custom mode")).expand_width(), 0.3)
+        .with_default_spacer()
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    Flex::row()
+                        .with_flex_child(
+                            TextBox::new()
+                                .with_placeholder("2560x1440@75 or a modeline")
+                                .lens(App::custom_mode)
+                                .expand_width(),
+                            1.,
+                        )
+                        .with_default_spacer()
+                        .with_child(
+                            Button::new("Add")
+                                .on_click(|_ctx, data: &mut App, _env| data.add_custom_mode()),
+                        ),
+                )
+                .with_child(
+                    Label::new(|data: &App, _env: &_| data.custom_mode_error.clone())
+                        .with_text_color(Color::rgb8(230, 90, 90)),
+                ),
+            0.7,
+        )
+        .with_spacer(2.)
+}
+
+const IDENTIFY_TITLE_PREFIX: &str = "twl-configurator identify: ";
+const IDENTIFY_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
+struct IdentifyController {
+    name: String,
+    place_timer: druid::TimerToken,
+    close_timer: druid::TimerToken,
+}
+
+impl<W: Widget<App>> Controller<App, W> for IdentifyController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        match event {
+            Event::WindowConnected => {
+                // Give the compositor a moment to map the window before moving it.
+                self.place_timer = ctx.request_timer(std::time::Duration::from_millis(100));
+                self.close_timer = ctx.request_timer(IDENTIFY_DURATION);
+            }
+            Event::Timer(id) if *id == self.place_timer => {
+                ctx.submit_command(commands::PLACE_IDENTIFY_WINDOW.with(self.name.clone()));
+            }
+            Event::Timer(id) if *id == self.close_timer => {
+                ctx.window().close();
+            }
+            _ => {}
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_identify_window(name: String) -> impl Widget<App> {
+    let label_name = name.clone();
+    let click_name = name.clone();
+
+    Label::new(move |data: &App, _env: &_| {
+        data.display_geo
+            .get(&label_name)
+            .map_or(label_name.clone(), |d| {
+                format!("{}\n{} {}", d.name, d.make, d.model)
+            })
+    })
+    .with_text_size(48.)
+    .with_text_alignment(druid::TextAlignment::Center)
+    .center()
+    .expand()
+    .background(Color::rgb8(30, 30, 30))
+    .on_click(move |ctx, data: &mut App, _env| {
+        for (n, d) in data.display_geo.iter_mut() {
+            d.focused = *n == click_name;
+        }
+        ctx.window().close();
+    })
+    .controller(IdentifyController {
+        name,
+        place_timer: druid::TimerToken::INVALID,
+        close_timer: druid::TimerToken::INVALID,
+    })
+}
+
+/// Opens a window on each active output, labelled with its name and model.
+fn identify_displays(ctx: &mut EventCtx, data: &App) {
+    for (name, d) in data.display_geo.iter() {
+        if !d.active {
+            continue;
+        }
+
+        ctx.new_window(
+            WindowDesc::new(build_identify_window(name.clone()))
+                .title(format!("{}{}", IDENTIFY_TITLE_PREFIX, name))
+                .show_titlebar(false)
+                .resizable(false)
+                .window_size((640., 240.)),
+        );
+    }
+}
+
+/// The history command for a key press: Ctrl+Z undoes and Ctrl+Shift+Z
+/// redoes. Matched on the character, so it follows the keyboard layout.
+fn history_shortcut(k: &druid::KeyEvent) -> Option<druid::Selector<()>> {
+    match &k.key {
+        druid::KbKey::Character(c) if k.mods.ctrl() && c.eq_ignore_ascii_case("z") => {
+            Some(if k.mods.shift() {
+                history_commands::REDO
+            } else {
+                history_commands::UNDO
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Keeps the history shortcuts away from the layout while a text box in
+/// the settings has focus, as the text boxes are all that take focus there.
+struct TextShortcutsController;
+
+impl<W: Widget<App>> Controller<App, W> for TextShortcutsController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(k) = event {
+            if ctx.has_focus() && history_shortcut(k).is_some() {
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Records edits to the displays for undo, and handles undo and redo.
+/// Edits made while a mouse button is held, or while a key repeats, are
+/// one step, so a drag is undone all at once. Only edits made by the user
+/// are recorded, not outputs changing or settings being applied.
+#[derive(Default)]
+struct HistoryController {
+    dragging: bool,
+    /// Whether the current drag or key repeat was already recorded.
+    recorded: bool,
+    /// Whether the last event other than a command was input, so that the
+    /// commands widgets send in response to it count as input too.
+    after_input: bool,
+}
+
+impl<W: Widget<App>> Controller<App, W> for HistoryController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut App,
+        env: &Env,
+    ) {
+        let by_user = match event {
+            Event::Command(c) if c.is(history_commands::UNDO) => {
+                data.undo();
+                ctx.set_handled();
+                return;
+            }
+            Event::Command(c) if c.is(history_commands::REDO) => {
+                data.redo();
+                ctx.set_handled();
+                return;
+            }
+            Event::Command(_) | Event::Notification(_) => self.after_input,
+            Event::MouseDown(_)
+            | Event::MouseUp(_)
+            | Event::MouseMove(_)
+            | Event::Wheel(_)
+            | Event::KeyDown(_)
+            | Event::KeyUp(_)
+            | Event::Paste(_)
+            | Event::Zoom(_) => {
+                self.after_input = true;
+                true
+            }
+            _ => {
+                self.after_input = false;
+                false
+            }
+        };
+        let continuing = match event {
+            Event::MouseDown(_) => {
+                self.dragging = true;
+                false
+            }
+            Event::KeyDown(k) => k.repeat,
+            _ => self.dragging,
+        };
+        if !continuing {
+            self.recorded = false;
+        }
+
+        let before = data.display_geo.clone();
+        child.event(ctx, event, data, env);
+        if by_user && !self.recorded && history::edited(&before, &data.display_geo) {
+            data.history.record(before);
+            self.recorded = true;
+        }
+
+        match event {
+            Event::KeyDown(k) if !ctx.is_handled() => {
+                if let Some(cmd) = history_shortcut(k) {
+                    ctx.submit_command(cmd);
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseUp(_) => self.dragging = false,
+            _ => {}
+        }
+    }
+}
+
+fn build_buttons(args: &Opt) -> impl Widget<App> {
+    let base_path = args.file.clone();
+
+    SizedBox::new(
+        Flex::row()
+            .must_fill_main_axis(true)
+            .main_axis_alignment(MainAxisAlignment::End)
+            .cross_axis_alignment(CrossAxisAlignment::Center)
+            .with_default_spacer()
+            .with_child(
+                Button::new("Undo").on_click(|ctx, _data: &mut App, _env| {
+                    ctx.submit_command(history_commands::UNDO)
+                }),
+            )
+            .with_spacer(2.)
+            .with_child(
+                Button::new("Redo").on_click(|ctx, _data: &mut App, _env| {
+                    ctx.submit_command(history_commands::REDO)
+                }),
+            )
+            .with_default_spacer()
+            .with_flex_child(
+                Button::new("Identify")
+                    .on_click(|ctx, data: &mut App, _env| identify_displays(ctx, data)),
+                0.3,
+            )
+            .with_default_spacer()
+            .with_flex_child(
+                Button::new("Apply now").on_click(|ctx, _data: &mut App, _env| {
+                    ctx.submit_command(commands::APPLY_DISPLAYS)
+                }),
+                0.3,
+            )
+            .with_default_spacer()
+            .with_flex_child(
+                Button::new("Save config").on_click(move |ctx, data: &mut App, _env| {
+                    // Check everything first so that nothing is written if
+                    // some of it can't be.
+                    if let Err(e) = data.night_light.validate() {
+                        data.notifications.error("Invalid night light settings", e);
+                        return;
+                    }
+
+                    let saved = data
+                        .save_config(base_path.clone())
+                        .map_err(|e| ("Failed to save the display config", e))
+                        .and_then(|()| {
+                            data.save_workspaces(&base_path)
+                                .map_err(|e| ("Failed to save the workspaces", e))
+                        })
+                        .and_then(|()| {
+                            data.night_light
+                                .save(&base_path)
+                                .map_err(|e| ("Failed to save the night light settings", e))
+                        });
+                    match saved {
+                        Ok(()) => {
+                            ctx.submit_command(RESTART_NIGHT_LIGHT_ACTION);
+                            data.notifications.info("Saved the display config")
+                        }
+                        Err((what, e)) => data.notifications.error(what, e),
+                    }
+                }),
+                0.3,
+            )
+            .with_default_spacer(),
+    )
+    .expand_width()
+}
+
+fn build_status_line() -> impl Widget<App> {
+    Label::new(|app: &App, _: &druid::Env| {
+        FocusedDisplay.with(app, |d| match d {
+            Some(d) => {
+                let (w, h) = d.logical_size();
+                let state = if !app.unapplied_settings(d).is_empty() {
+                    ", not applied"
+                } else if app.is_unsaved(d) {
+                    ", not saved"
+                } else {
+                    ""
+                };
+                format!(
+                    "{} ({} {}) at {}, {}, size {}x{}{}",
+                    d.name, d.make, d.model, d.position.0, d.position.1, w, h, state
+                )
+            }
+            None => "Tab selects an output, arrow keys move it \
+                     (Shift: 16px, Ctrl: to the next edge), Ctrl+Z undoes"
+                .to_string(),
+        })
+    })
+    .with_text_color(Color::rgb8(200, 200, 200))
+    .padding(4.)
+    .expand_width()
+}
+
+pub fn build_ui(args: &Opt) -> impl Widget<App> {
+    Flex::column()
+        .with_child(
+            build_notifications()
+                .lens(App::notifications)
+                .padding(2.)
+                .expand_width(),
+        )
+        .with_flex_child(
+            Split::rows(
+                Flex::column()
+                    .with_flex_child(
+                        Memo::new(
+                            MonitorView::default(),
+                            App::marked_displays,
+                            App::put_marked_displays,
+                        ),
+                        1.,
+                    )
+                    .with_child(build_status_line()),
                 Flex::column()
                     .must_fill_main_axis(true)
-                    .main_axis_alignment(MainAxisAlignment::Start)
+                    .main_axis_alignment(MainAxisAlignment::SpaceBetween)
                     .cross_axis_alignment(CrossAxisAlignment::Center)
-                    .with_child(build_name_row())
-                    .with_child(build_info_row())
-                    .with_default_spacer()
-                    .with_child(build_pos_input())
-                    .with_spacer(2.)
-                    .with_child(build_scale_input())
-                    .with_default_spacer()
-                    .with_child(build_rotation_input())
+                    .with_flex_child(
+                        Flex::column()
+                            .must_fill_main_axis(true)
+                            .main_axis_alignment(MainAxisAlignment::Start)
+                            .cross_axis_alignment(CrossAxisAlignment::Center)
+                            .with_child(build_revert_banner())
+                            .with_child(build_review_input())
+                            .with_child(build_name_row())
+                            .with_child(build_info_row())
+                            .with_child(build_identifier_input())
+                            .with_default_spacer()
+                            .with_child(build_profile_row(args))
+                            .with_default_spacer()
+                            .with_child(build_enabled_input())
+                            .with_spacer(2.)
+                            .with_child(build_pos_input())
+                            .with_spacer(2.)
+                            .with_child(build_scale_input())
+                            .with_child(build_brightness_input())
+                            .with_default_spacer()
+                            .with_child(build_rotation_input())
+                            .with_default_spacer()
+                            .with_child(build_advanced_input())
+                            .with_spacer(2.)
+                            .with_child(build_background_input())
+                            .with_spacer(2.)
+                            .with_child(build_workspace_input())
+                            .with_spacer(2.)
+                            .with_child(build_night_light_input())
+                            .with_default_spacer()
+                            .with_flex_child(build_mode_input(), 0.5)
+                            .with_spacer(2.)
+                            .with_child(build_custom_mode_input())
+                            .controller(TextShortcutsController {}),
+                        0.9,
+                    )
                     .with_default_spacer()
-                    .with_flex_child(build_mode_input(), 0.5),
-                0.9,
+                    .with_child(build_buttons(args))
+                    .padding(2.),
             )
-            .with_default_spacer()
-            .with_child(build_buttons(args))
-            .padding(2.),
-    )
-    .bar_size(2.)
-    .solid_bar(true)
-    .draggable(true)
+            .bar_size(2.)
+            .solid_bar(true)
+            .draggable(true),
+            1.,
+        )
+        .controller(HistoryController::default())
     //.debug_paint_layout()
 }
@@ -1,19 +1,63 @@
+pub mod backend;
 pub mod bluetooth;
+pub mod cli;
 pub mod display;
 pub mod lens;
 pub mod model;
+pub mod wallpaper;
 pub mod widgets;
 
+use model::display::{Mode, Pos, Scale, Transform};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[allow(non_camel_case_types)]
 #[derive(StructOpt, Debug, PartialEq, Clone)]
 pub enum Cmd {
-    /// Open a UI to configure the displays.
-    Display,
+    /// Open a UI to configure the displays, or change them from the command
+    /// line.
+    Display {
+        #[structopt(subcommand)]
+        cmd: Option<DisplayCmd>,
+    },
     /// Open a UI to configure bluetooth.
     Bluetooth,
+    /// Cycle the wallpaper of an output through a directory of images.
+    RotateWallpaper(wallpaper::RotateOpts),
+}
+
+#[derive(StructOpt, Debug, PartialEq, Clone)]
+pub enum DisplayCmd {
+    /// List the connected outputs.
+    List {
+        /// Print the outputs as JSON.
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Change the settings of an output right away.
+    Set {
+        /// The connector name of the output, such as DP-1, or its make,
+        /// model and serial.
+        output: String,
+        #[structopt(long)]
+        scale: Option<Scale>,
+        /// The position of the output, as x,y.
+        #[structopt(long)]
+        pos: Option<Pos>,
+        /// The mode of the output, as <width>x<height>[@<refresh>].
+        #[structopt(long)]
+        mode: Option<Mode>,
+        #[structopt(long)]
+        transform: Option<Transform>,
+        #[structopt(long, conflicts_with = "disable")]
+        enable: bool,
+        #[structopt(long)]
+        disable: bool,
+    },
+    /// Apply the saved config to the connected outputs.
+    Apply,
+    /// Save the current settings of the outputs to the config.
+    Save,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -31,6 +75,23 @@ pub struct Opt {
         default_value = "~/.config/sway/twl"
     )]
     pub file: PathBuf,
+    #[structopt(
+        name = "wallpaper directory",
+        long = "wallpapers",
+        default_value = "~/Pictures/wallpapers"
+    )]
+    pub wallpapers: PathBuf,
+    /// Refer to all outputs by make, model and serial when saving the config,
+    /// instead of by connector.
+    #[structopt(long = "use_identifiers")]
+    pub use_identifiers: bool,
+    /// How to talk to the compositor: sway, wlr for other compositors with
+    /// wlr-output-management, or fake for simulated outputs.
+    #[structopt(long = "backend", default_value = "sway")]
+    pub backend: backend::BackendKind,
+    /// Where sysfs is mounted, to find the backlights under.
+    #[structopt(long = "sysfs_root", default_value = "/sys")]
+    pub sysfs_root: PathBuf,
 }
 
 use druid::{widget::Controller, Env, Event, EventCtx, Widget};
@@ -5,28 +5,91 @@ use configurator::{Cmd, EscExiter, Opt};
 
 fn main() -> Result<(), PlatformError> {
     let mut args = Opt::from_args();
-    if args.file.starts_with("~") {
-        args.file = args
-            .file
-            .to_str()
-            .unwrap()
-            .replace("~", home::home_dir().unwrap().to_str().unwrap())
-            .into();
+    for path in [&mut args.file, &mut args.wallpapers] {
+        if let Ok(rest) = path.strip_prefix("~") {
+            match home::home_dir() {
+                Some(home) => *path = home.join(rest),
+                None => {
+                    eprintln!("error: no home directory to find {} in", path.display());
+                    std::process::exit(1);
+                }
+            }
+        }
     }
 
     match &args.cmd {
-        Cmd::Display => {
-            use configurator::{display::build_ui, model::display::App};
+        Cmd::Display { cmd: Some(cmd) } => {
+            if let Err(e) = configurator::cli::run_display(cmd, &args) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
 
-            let mut conn = swayipc::Connection::new().unwrap();
-            let model: App = conn.get_outputs().unwrap().into();
+        Cmd::Display { cmd: None } => {
+            use configurator::{
+                backend::open as open_backend,
+                display::{build_ui, watch_outputs, DisplayDelegate},
+                model::display::App,
+                model::nightlight::NightLight,
+            };
 
-            return AppLauncher::with_window(
+            let mut backend = open_backend(args.backend);
+            let mut model: App = match backend.outputs() {
+                Ok(outputs) => outputs.into(),
+                Err(e) => {
+                    let mut model = App::default();
+                    model
+                        .notifications
+                        .error("Could not read the outputs (is sway running?)", e);
+                    model
+                }
+            };
+            model.use_identifiers = args.use_identifiers;
+            if let Err(e) = model.load_config(args.file.clone()) {
+                model
+                    .notifications
+                    .error("Failed to read the display config", e);
+            }
+            if let Err(e) = model.load_profiles(args.file.clone()) {
+                model
+                    .notifications
+                    .error("Failed to read the display profiles", e);
+            }
+            if let Err(e) = model.load_wallpapers(&args.wallpapers) {
+                model
+                    .notifications
+                    .error("Failed to read the wallpaper directory", e);
+            }
+            if let Err(e) = model.load_workspaces(&args.file, &mut *backend) {
+                model
+                    .notifications
+                    .error("Failed to read the workspaces", e);
+            }
+            if let Err(e) = model.load_backlights(&args.sysfs_root) {
+                model
+                    .notifications
+                    .error("Failed to read the backlights", e);
+            }
+            match NightLight::load(&args.file) {
+                Ok(night_light) => model.night_light = night_light,
+                Err(e) => model
+                    .notifications
+                    .error("Failed to read the night light settings", e),
+            }
+            if !model.display_geo.is_empty() {
+                model.apply_matching_profile(&mut *backend);
+            }
+
+            let launcher = AppLauncher::with_window(
                 WindowDesc::new(build_ui(&args).controller(EscExiter {}))
                     .title("TwitchyLinux - Configure display")
                     .window_size((600.0, 700.0)),
-            )
-            .launch(model);
+            );
+
+            watch_outputs(launcher.get_external_handle(), &*backend);
+            return launcher
+                .delegate(DisplayDelegate::new(backend))
+                .launch(model);
         }
 
         Cmd::Bluetooth => {
@@ -47,6 +110,13 @@ fn main() -> Result<(), PlatformError> {
                 .delegate(BluetoothDelegate::new(sink))
                 .launch(model);
         }
+
+        Cmd::RotateWallpaper(opts) => {
+            if let Err(e) = configurator::wallpaper::rotate(opts) {
+                eprintln!("failed to rotate wallpaper: {}", e);
+                std::process::exit(1);
+            }
+        }
     }
 
     Ok(())
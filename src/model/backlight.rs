@@ -0,0 +1,302 @@
+//! Screen and keyboard backlights, read from sysfs. Brightness is changed
+//! through logind, which doesn't need root.
+
+use druid::im::Vector;
+use druid::{Data, Lens};
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+/// The sysfs root of the running system. Backlights under any other root
+/// are a fake tree, and are written to directly.
+pub const SYSFS_ROOT: &str = "/sys";
+
+/// The shortest time between two changes of the brightness, so that
+/// dragging a slider doesn't start a flood of busctl calls.
+const APPLY_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum Kind {
+    Screen,
+    Keyboard,
+}
+
+impl Kind {
+    /// The sysfs class of backlights of this kind, which logind calls the
+    /// subsystem.
+    fn subsystem(&self) -> &'static str {
+        match self {
+            Kind::Screen => "backlight",
+            Kind::Keyboard => "leds",
+        }
+    }
+}
+
+#[derive(Clone, Data, Debug, Lens)]
+pub struct Backlight {
+    pub kind: Kind,
+    /// The device name, such as intel_backlight.
+    pub name: String,
+    /// The connector of the output which the backlight belongs to.
+    pub output: Option<String>,
+    /// Brightness as a fraction of the maximum.
+    pub level: f64,
+
+    max: u32,
+    #[data(ignore)]
+    path: PathBuf,
+    via_logind: bool,
+}
+
+fn read_u32(path: &Path) -> Result<u32, std::io::Error> {
+    std::fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Whether the connector is a built-in panel, which is what keyboard
+/// backlights and unmatched screen backlights belong to.
+fn is_internal(connector: &str) -> bool {
+    ["eDP-", "LVDS-", "DSI-"]
+        .iter()
+        .any(|p| connector.starts_with(p))
+}
+
+/// Finds the connector of a screen backlight. Backlight devices are
+/// children of their connector, as in /sys/class/drm/card0-eDP-1/intel_backlight.
+fn find_connector(root: &Path, name: &str) -> Result<Option<String>, std::io::Error> {
+    let drm = match std::fs::read_dir(root.join("class/drm")) {
+        Ok(drm) => drm,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    for entry in drm {
+        let entry = entry?;
+        if entry.path().join(name).exists() {
+            let dir = entry.file_name().to_string_lossy().into_owned();
+            // The connector directories are named card<n>-<connector>.
+            if let Some((_, connector)) = dir.split_once('-') {
+                return Ok(Some(connector.to_string()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+impl Backlight {
+    fn read(kind: Kind, path: PathBuf, via_logind: bool) -> Result<Self, std::io::Error> {
+        let max = read_u32(&path.join("max_brightness"))?;
+        let brightness = read_u32(&path.join("brightness"))?;
+
+        Ok(Backlight {
+            kind,
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            output: None,
+            level: if max > 0 {
+                brightness as f64 / max as f64
+            } else {
+                0.
+            },
+            max,
+            path,
+            via_logind,
+        })
+    }
+
+    pub fn brightness(&self) -> u32 {
+        (self.level.clamp(0., 1.) * self.max as f64).round() as u32
+    }
+
+    /// Sets the brightness of the device to `level`.
+    pub fn apply(&self) -> Result<(), std::io::Error> {
+        if !self.via_logind {
+            return std::fs::write(self.path.join("brightness"), self.brightness().to_string());
+        }
+
+        let status = std::process::Command::new("busctl")
+            .args([
+                "call",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1/session/auto",
+                "org.freedesktop.login1.Session",
+                "SetBrightness",
+                "ssu",
+                self.kind.subsystem(),
+                &self.name,
+                &self.brightness().to_string(),
+            ])
+            .status()?;
+        if !status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("logind refused to set the brightness of {}", self.name),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Lists the screen and keyboard backlights under the sysfs root, and ties
+/// them to the connectors of the outputs they belong to.
+pub fn list(root: &Path, connectors: &[String]) -> Result<Vector<Backlight>, std::io::Error> {
+    let via_logind = root == Path::new(SYSFS_ROOT);
+    let internal = connectors.iter().find(|c| is_internal(c)).cloned();
+    let mut out = Vector::new();
+
+    for kind in [Kind::Screen, Kind::Keyboard] {
+        let dir = match std::fs::read_dir(root.join("class").join(kind.subsystem())) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        let mut paths: Vec<PathBuf> = dir
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                kind == Kind::Screen
+                    || p.file_name()
+                        .map_or(false, |n| n.to_string_lossy().contains("kbd_backlight"))
+            })
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let mut backlight = Backlight::read(kind, path, via_logind)?;
+            backlight.output = match kind {
+                Kind::Screen => find_connector(root, &backlight.name)?,
+                Kind::Keyboard => None,
+            }
+            .or_else(|| internal.clone());
+            out.push_back(backlight);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Sets brightness levels on a background thread, so the UI never waits on
+/// logind. Levels set faster than they can be applied are skipped, only
+/// the latest level of each backlight is applied.
+pub struct Worker {
+    levels: Sender<Backlight>,
+}
+
+impl Worker {
+    /// Starts the thread. Failures are passed to `on_error` along with the
+    /// name of the backlight.
+    pub fn new<F>(on_error: F) -> Self
+    where
+        F: Fn(&str, std::io::Error) + Send + 'static,
+    {
+        let (levels, rx) = mpsc::channel::<Backlight>();
+        std::thread::spawn(move || {
+            while let Ok(first) = rx.recv() {
+                let mut pending = vec![first];
+                for b in rx.try_iter() {
+                    pending.retain(|p| p.path != b.path);
+                    pending.push(b);
+                }
+
+                for b in pending {
+                    if let Err(e) = b.apply() {
+                        on_error(&b.name, e);
+                    }
+                }
+                std::thread::sleep(APPLY_INTERVAL);
+            }
+        });
+        Worker { levels }
+    }
+
+    pub fn set(&self, backlight: &Backlight) {
+        // The thread only stops once we are dropped.
+        let _ = self.levels.send(backlight.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake sysfs tree with a laptop panel, a keyboard backlight and an
+    /// LED which isn't a backlight.
+    fn fake_sysfs(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("twl-sysfs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        let device = |class: &str, name: &str, max: u32, brightness: u32| {
+            let dir = root.join("class").join(class).join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("max_brightness"), format!("{}\n", max)).unwrap();
+            std::fs::write(dir.join("brightness"), format!("{}\n", brightness)).unwrap();
+        };
+        device("backlight", "intel_backlight", 1000, 500);
+        device("leds", "tpacpi::kbd_backlight", 2, 2);
+        device("leds", "input3::capslock", 1, 0);
+        std::fs::create_dir_all(root.join("class/drm/card0-eDP-1/intel_backlight")).unwrap();
+        std::fs::create_dir_all(root.join("class/drm/card0-DP-1")).unwrap();
+        root
+    }
+
+    #[test]
+    fn lists_backlights_with_their_outputs() {
+        let root = fake_sysfs("list");
+        let connectors = ["DP-1".to_string(), "eDP-1".to_string()];
+        let backlights = list(&root, &connectors).unwrap();
+
+        assert_eq!(backlights.len(), 2);
+        let screen = &backlights[0];
+        assert_eq!(screen.kind, Kind::Screen);
+        assert_eq!(screen.name, "intel_backlight");
+        assert_eq!(screen.output.as_deref(), Some("eDP-1"));
+        assert_eq!(screen.level, 0.5);
+        assert!(!screen.via_logind);
+
+        let keyboard = &backlights[1];
+        assert_eq!(keyboard.kind, Kind::Keyboard);
+        assert_eq!(keyboard.name, "tpacpi::kbd_backlight");
+        assert_eq!(keyboard.output.as_deref(), Some("eDP-1"));
+        assert_eq!(keyboard.level, 1.);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn finds_the_connector_of_a_backlight() {
+        let root = fake_sysfs("connector");
+        assert_eq!(
+            find_connector(&root, "intel_backlight").unwrap().as_deref(),
+            Some("eDP-1")
+        );
+        assert_eq!(find_connector(&root, "acpi_video0").unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // No drm class at all.
+        assert_eq!(find_connector(&root, "intel_backlight").unwrap(), None);
+    }
+
+    #[test]
+    fn scales_levels_to_the_device() {
+        let root = fake_sysfs("scale");
+        let mut backlight = list(&root, &[]).unwrap()[0].clone();
+
+        backlight.level = 0.25;
+        backlight.apply().unwrap();
+        let written =
+            std::fs::read_to_string(root.join("class/backlight/intel_backlight/brightness"));
+        assert_eq!(written.unwrap(), "250");
+
+        backlight.level = 1.5;
+        assert_eq!(backlight.brightness(), 1000);
+        backlight.level = -1.;
+        assert_eq!(backlight.brightness(), 0);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
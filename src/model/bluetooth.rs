@@ -1,6 +1,8 @@
 use druid::im::{HashMap, Vector};
 use druid::{Data, Lens};
 
+use crate::model::notification::Notifications;
+
 pub mod commands {
     use druid::im::Vector;
     use druid::Selector;
@@ -22,6 +24,8 @@ pub struct App {
     pub status_text: String,
 
     pub devices: Vector<Device>,
+
+    pub notifications: Notifications,
 }
 
 #[derive(Clone, Default, Data, Debug, Lens)]
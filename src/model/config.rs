@@ -0,0 +1,168 @@
+//! Writing of the config files we manage, keeping whatever the user added
+//! to them by hand.
+
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Merges generated blocks of lines into the existing contents of a config
+/// file. `key_of` identifies the lines we manage; these are replaced by the
+/// blocks, sorted by key, where the first of them was. Blocks are appended
+/// to a file which has none of our lines yet. Comments and all other lines
+/// are kept as they are.
+pub fn merge<F>(existing: &str, header: &str, blocks: &[(String, Vec<String>)], key_of: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut sorted: Vec<&(String, Vec<String>)> = blocks.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = String::with_capacity(existing.len() + 200);
+    let mut written = false;
+    let mut write_blocks = |out: &mut String| {
+        if written {
+            return;
+        }
+        for (_, block) in &sorted {
+            for l in block {
+                out.push_str(l);
+                out.push('\n');
+            }
+        }
+        written = true;
+    };
+
+    if existing.trim().is_empty() {
+        out.push_str(header);
+        out.push_str("\n\n");
+    }
+
+    for line in existing.lines() {
+        if key_of(line).is_some() {
+            write_blocks(&mut out);
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    write_blocks(&mut out);
+
+    out
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(suffix);
+    p.into()
+}
+
+/// Reads a config file, treating a missing file as empty.
+pub fn read(path: &Path) -> Result<String, std::io::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(c) => Ok(c),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces the file with the given contents by writing them to a temporary
+/// file and renaming it over the original, so the file is never left half
+/// written. The previous version is kept with a `.bak` suffix. A symlinked
+/// file is written through the link, and keeps its permissions.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), std::io::Error> {
+    let path = match std::fs::canonicalize(path) {
+        Ok(p) => p,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let tmp = with_suffix(&path, &format!(".{}.tmp", std::process::id()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        if let Ok(meta) = std::fs::metadata(&path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+
+        if path.exists() {
+            std::fs::copy(&path, with_suffix(&path, ".bak"))?;
+        }
+        std::fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_of(line: &str) -> Option<String> {
+        line.strip_prefix("output ")
+            .map(|l| l.split(' ').next().unwrap_or_default().to_string())
+    }
+
+    fn block(key: &str) -> (String, Vec<String>) {
+        (key.to_string(), vec![format!("output {} enable", key)])
+    }
+
+    /// A fresh directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twl-config-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn merge_sorts_blocks_and_keeps_other_lines() {
+        let existing = "# mine\noutput HDMI-A-1 disable\nset $x 1\noutput DP-1 disable\n";
+        let blocks = [block("eDP-1"), block("DP-1"), block("HDMI-A-1")];
+        assert_eq!(
+            merge(existing, "# header", &blocks, key_of),
+            "# mine\noutput DP-1 enable\noutput HDMI-A-1 enable\noutput eDP-1 enable\nset $x 1\n"
+        );
+    }
+
+    #[test]
+    fn merge_into_empty_file_adds_header() {
+        let blocks = [block("DP-2"), block("DP-1")];
+        assert_eq!(
+            merge("", "# header", &blocks, key_of),
+            "# header\n\noutput DP-1 enable\noutput DP-2 enable\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_follows_symlinks_and_keeps_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = temp_dir("symlink");
+        let target = dir.join("displays");
+        let link = dir.join("link");
+        std::fs::write(&target, "old\n").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "new\n").unwrap();
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("displays.bak")).unwrap(),
+            "old\n"
+        );
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
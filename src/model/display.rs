@@ -1,15 +1,42 @@
 use druid::im::{HashMap, Vector};
 use druid::{Data, Lens};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use swayipc::Output;
 
+use crate::backend::{BackendError, Change, OutputBackend};
+use crate::model::backlight::{self, Backlight};
+use crate::model::config;
+use crate::model::history::History;
+use crate::model::nightlight::NightLight;
+use crate::model::notification::Notifications;
+use crate::model::profile::Profile;
+use crate::model::workspace::{self, Workspace};
+use crate::wallpaper::{self, RotateOpts};
+
+pub mod commands {
+    use druid::Selector;
+
+    // worker -> UI commands
+    pub const OUTPUTS_CHANGED: Selector<Vec<super::Display>> = Selector::new("outputs_changed");
+
+    // UI -> delegate commands
+    pub const APPLY_DISPLAYS: Selector<()> = Selector::new("apply_displays");
+    pub const REVERT_DISPLAYS: Selector<()> = Selector::new("revert_displays");
+    /// Moves the identify window of the named output onto it.
+    pub const PLACE_IDENTIFY_WINDOW: Selector<String> = Selector::new("place_identify_window");
+}
+
 #[derive(Clone, Copy, Data, Debug, PartialEq)]
 pub enum Transform {
     None,
     R90,
     R180,
     R270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270,
 }
 
 impl Default for Transform {
@@ -18,16 +45,61 @@ impl Default for Transform {
     }
 }
 
+impl Transform {
+    /// How many quarter turns the output is rotated by.
+    pub fn quarter_turns(&self) -> u32 {
+        match self {
+            Transform::None | Transform::Flipped => 0,
+            Transform::R90 | Transform::Flipped90 => 1,
+            Transform::R180 | Transform::Flipped180 => 2,
+            Transform::R270 | Transform::Flipped270 => 3,
+        }
+    }
+
+    /// Whether the output is mirrored, before being rotated.
+    pub fn is_flipped(&self) -> bool {
+        matches!(
+            self,
+            Transform::Flipped
+                | Transform::Flipped90
+                | Transform::Flipped180
+                | Transform::Flipped270
+        )
+    }
+}
+
+impl std::str::FromStr for Transform {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" | "0" => Ok(Transform::None),
+            "90" => Ok(Transform::R90),
+            "180" => Ok(Transform::R180),
+            "270" => Ok(Transform::R270),
+            "flipped" => Ok(Transform::Flipped),
+            "flipped-90" => Ok(Transform::Flipped90),
+            "flipped-180" => Ok(Transform::Flipped180),
+            "flipped-270" => Ok(Transform::Flipped270),
+            _ => Err(ConfigParseError(format!("unknown transform {:?}", s))),
+        }
+    }
+}
+
 impl std::fmt::Display for Transform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
             match self {
+                Transform::None => "normal",
                 Transform::R90 => "90",
                 Transform::R180 => "180",
                 Transform::R270 => "270",
-                _ => "normal",
+                Transform::Flipped => "flipped",
+                Transform::Flipped90 => "flipped-90",
+                Transform::Flipped180 => "flipped-180",
+                Transform::Flipped270 => "flipped-270",
             }
         )
     }
@@ -42,17 +114,268 @@ impl From<f64> for Scale {
     }
 }
 
+/// The largest scale accepted.
+pub const MAX_SCALE: f64 = 10.;
+
+/// Fractional scales are in steps of 1/120, as in wp-fractional-scale.
+const SCALE_STEPS: u32 = 120;
+
+impl Scale {
+    /// The scale as written to sway, precise enough to round to the right
+    /// 1/120 step, but with at least two decimals like before.
+    fn command(&self) -> String {
+        let s = self.to_string();
+        match s.find('.') {
+            Some(dot) if s.len() - dot > 2 => s,
+            _ => format!("{:.2}", self.0),
+        }
+    }
+
+    /// Checks the scale is one that can be entered. Scales already in the
+    /// config are left for sway to judge.
+    pub fn validate(&self) -> Result<(), ConfigParseError> {
+        if !(self.0 > 0. && self.0 <= MAX_SCALE) {
+            return Err(ConfigParseError(format!(
+                "scale must be above 0 and at most {}",
+                MAX_SCALE
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl std::str::FromStr for Scale {
-    type Err = std::num::ParseFloatError;
+    type Err = ConfigParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Scale(s.parse::<f64>()?))
+        let scale: f64 = s
+            .trim()
+            .parse()
+            .map_err(|_| ConfigParseError(format!("invalid scale {:?}", s)))?;
+        Ok(Scale(scale))
     }
 }
 
 impl std::fmt::Display for Scale {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        let s = format!("{:.4}", self.0);
+        f.write_str(s.trim_end_matches('0').trim_end_matches('.'))
+    }
+}
+
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum Subpixel {
+    Unknown,
+    None,
+    Rgb,
+    Bgr,
+    Vrgb,
+    Vbgr,
+}
+
+impl Default for Subpixel {
+    fn default() -> Subpixel {
+        Subpixel::Unknown
+    }
+}
+
+impl std::str::FromStr for Subpixel {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Subpixel::Unknown),
+            "none" => Ok(Subpixel::None),
+            "rgb" => Ok(Subpixel::Rgb),
+            "bgr" => Ok(Subpixel::Bgr),
+            "vrgb" => Ok(Subpixel::Vrgb),
+            "vbgr" => Ok(Subpixel::Vbgr),
+            _ => Err(ConfigParseError(format!("unknown subpixel layout {:?}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for Subpixel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Subpixel::None => "none",
+                Subpixel::Rgb => "rgb",
+                Subpixel::Bgr => "bgr",
+                Subpixel::Vrgb => "vrgb",
+                Subpixel::Vbgr => "vbgr",
+                _ => "unknown",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum RenderBitDepth {
+    Six,
+    Eight,
+    Ten,
+}
+
+impl Default for RenderBitDepth {
+    fn default() -> RenderBitDepth {
+        RenderBitDepth::Eight
+    }
+}
+
+impl std::str::FromStr for RenderBitDepth {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "6" => Ok(RenderBitDepth::Six),
+            "8" => Ok(RenderBitDepth::Eight),
+            "10" => Ok(RenderBitDepth::Ten),
+            _ => Err(ConfigParseError(format!(
+                "unknown render bit depth {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for RenderBitDepth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RenderBitDepth::Six => "6",
+                RenderBitDepth::Ten => "10",
+                _ => "8",
+            }
+        )
+    }
+}
+
+/// The `max_render_time` of an output in milliseconds, where 0 means off.
+#[derive(Clone, Copy, Data, Debug, Default, PartialEq)]
+pub struct MaxRenderTime(pub u32);
+
+impl std::str::FromStr for MaxRenderTime {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" | "" => Ok(MaxRenderTime(0)),
+            ms => Ok(MaxRenderTime(ms.parse::<u32>()?)),
+        }
+    }
+}
+
+impl std::fmt::Display for MaxRenderTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => write!(f, "off"),
+            ms => write!(f, "{}", ms),
+        }
+    }
+}
+
+fn parse_toggle(s: &str) -> Result<bool, ConfigParseError> {
+    match s {
+        "on" | "yes" | "true" | "enable" => Ok(true),
+        "off" | "no" | "false" | "disable" => Ok(false),
+        _ => Err(ConfigParseError(format!("expected on or off, got {:?}", s))),
+    }
+}
+
+/// How the background image is fitted to an output, or `SolidColor` if the
+/// background is a plain colour.
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum BackgroundMode {
+    Stretch,
+    Fill,
+    Fit,
+    Center,
+    Tile,
+    SolidColor,
+}
+
+impl Default for BackgroundMode {
+    fn default() -> BackgroundMode {
+        BackgroundMode::Fill
+    }
+}
+
+impl std::str::FromStr for BackgroundMode {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stretch" => Ok(BackgroundMode::Stretch),
+            "fill" => Ok(BackgroundMode::Fill),
+            "fit" => Ok(BackgroundMode::Fit),
+            "center" => Ok(BackgroundMode::Center),
+            "tile" => Ok(BackgroundMode::Tile),
+            "solid_color" => Ok(BackgroundMode::SolidColor),
+            _ => Err(ConfigParseError(format!("unknown background mode {:?}", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for BackgroundMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BackgroundMode::Stretch => "stretch",
+                BackgroundMode::Fill => "fill",
+                BackgroundMode::Fit => "fit",
+                BackgroundMode::Center => "center",
+                BackgroundMode::Tile => "tile",
+                BackgroundMode::SolidColor => "solid_color",
+            }
+        )
+    }
+}
+
+/// The `bg` of an output.
+#[derive(Clone, Data, Debug, Default, PartialEq, Lens)]
+pub struct Background {
+    /// Path to the image, or a `#rrggbb` colour when the mode is
+    /// `SolidColor`. Empty if the output has no background set.
+    pub source: String,
+    pub mode: BackgroundMode,
+    /// Minutes between switching to the next image in the same directory,
+    /// or 0 to always show the same image.
+    pub rotate_minutes: u32,
+}
+
+impl Background {
+    /// Whether there is a background which sway would accept.
+    pub fn is_set(&self) -> bool {
+        match self.mode {
+            BackgroundMode::SolidColor => self.rgb().is_some(),
+            _ => !self.source.is_empty(),
+        }
+    }
+
+    /// Whether the background cycles through the images of a directory.
+    pub fn rotates(&self) -> bool {
+        self.rotate_minutes > 0 && self.mode != BackgroundMode::SolidColor && self.is_set()
+    }
+
+    /// The colour of a `SolidColor` background.
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let hex = self.source.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    fn command(&self) -> String {
+        format!("bg {} {}", quote_arg(&self.source), self.mode)
     }
 }
 
@@ -97,12 +420,227 @@ impl std::fmt::Display for Pos {
     }
 }
 
-#[derive(Clone, Default, Data, Debug, Lens)]
+/// Detailed timings for a mode, in the format used by xorg modelines.
+#[derive(Clone, Data, Debug, PartialEq)]
+pub struct Modeline {
+    /// Pixel clock in MHz.
+    pub clock: f64,
+    pub hdisplay: i32,
+    pub hsync_start: i32,
+    pub hsync_end: i32,
+    pub htotal: i32,
+    pub vdisplay: i32,
+    pub vsync_start: i32,
+    pub vsync_end: i32,
+    pub vtotal: i32,
+    pub hsync: String,
+    pub vsync: String,
+}
+
+impl Modeline {
+    /// The refresh rate of the modeline in mHz.
+    pub fn refresh(&self) -> i32 {
+        (self.clock * 1_000_000_000. / (self.htotal as f64 * self.vtotal as f64)).round() as i32
+    }
+}
+
+impl std::str::FromStr for Modeline {
+    type Err = ConfigParseError;
+
+    /// Parses a modeline, optionally preceded by the `Modeline` keyword and
+    /// a quoted name as printed by `cvt` or `gtf`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = split_args(s);
+        if args
+            .first()
+            .map_or(false, |a| a.eq_ignore_ascii_case("modeline"))
+        {
+            args.remove(0);
+        }
+        if args.len() == 12 {
+            args.remove(0);
+        }
+        if args.len() != 11 {
+            return Err(ConfigParseError(format!(
+                "a modeline needs 11 values, got {}",
+                args.len()
+            )));
+        }
+
+        let int = |i: usize| {
+            args[i]
+                .parse::<i32>()
+                .map_err(|_| ConfigParseError(format!("invalid modeline value {:?}", args[i])))
+        };
+        let ml = Modeline {
+            clock: args[0]
+                .parse::<f64>()
+                .map_err(|_| ConfigParseError(format!("invalid pixel clock {:?}", args[0])))?,
+            hdisplay: int(1)?,
+            hsync_start: int(2)?,
+            hsync_end: int(3)?,
+            htotal: int(4)?,
+            vdisplay: int(5)?,
+            vsync_start: int(6)?,
+            vsync_end: int(7)?,
+            vtotal: int(8)?,
+            hsync: args[9].to_lowercase(),
+            vsync: args[10].to_lowercase(),
+        };
+
+        if ml.clock.is_nan() || ml.clock <= 0. {
+            return Err(ConfigParseError("the pixel clock must be positive".into()));
+        }
+        if !(0 < ml.hdisplay
+            && ml.hdisplay <= ml.hsync_start
+            && ml.hsync_start <= ml.hsync_end
+            && ml.hsync_end <= ml.htotal)
+        {
+            return Err(ConfigParseError(
+                "horizontal timings must be increasing".into(),
+            ));
+        }
+        if !(0 < ml.vdisplay
+            && ml.vdisplay <= ml.vsync_start
+            && ml.vsync_start <= ml.vsync_end
+            && ml.vsync_end <= ml.vtotal)
+        {
+            return Err(ConfigParseError(
+                "vertical timings must be increasing".into(),
+            ));
+        }
+        if ml.hsync != "+hsync" && ml.hsync != "-hsync" {
+            return Err(ConfigParseError(format!("invalid hsync {:?}", ml.hsync)));
+        }
+        if ml.vsync != "+vsync" && ml.vsync != "-vsync" {
+            return Err(ConfigParseError(format!("invalid vsync {:?}", ml.vsync)));
+        }
+
+        Ok(ml)
+    }
+}
+
+impl std::fmt::Display for Modeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {} {} {}",
+            self.clock,
+            self.hdisplay,
+            self.hsync_start,
+            self.hsync_end,
+            self.htotal,
+            self.vdisplay,
+            self.vsync_start,
+            self.vsync_end,
+            self.vtotal,
+            self.hsync,
+            self.vsync
+        )
+    }
+}
+
+#[derive(Clone, Default, Data, Debug, PartialEq, Lens)]
 pub struct Mode {
     pub width: i32,
     pub height: i32,
     pub refresh: i32,
     pub selected: bool,
+
+    /// Whether the mode was entered by the user rather than advertised
+    /// by the output.
+    pub custom: bool,
+    pub modeline: Option<Modeline>,
+}
+
+impl Mode {
+    /// Parses a user-entered custom mode, either as
+    /// `<width>x<height>[@<refresh>]` or as a full modeline.
+    pub fn parse_custom(s: &str) -> Result<Mode, ConfigParseError> {
+        let s = s.trim();
+        let mut m = if s.contains('x') && !s.contains(char::is_whitespace) {
+            s.parse::<Mode>()?
+        } else {
+            let ml: Modeline = s.parse()?;
+            Mode {
+                width: ml.hdisplay,
+                height: ml.vdisplay,
+                refresh: ml.refresh(),
+                modeline: Some(ml),
+                ..Mode::default()
+            }
+        };
+
+        if m.width <= 0 || m.height <= 0 || m.width > 16384 || m.height > 16384 {
+            return Err(ConfigParseError(format!(
+                "{}x{} is not a valid resolution",
+                m.width, m.height
+            )));
+        }
+        if m.refresh != 0 && !(1_000..=1_000_000).contains(&m.refresh) {
+            return Err(ConfigParseError(format!(
+                "{:.2} Hz is not a valid refresh rate",
+                m.refresh as f64 / 1000.
+            )));
+        }
+
+        m.custom = true;
+        Ok(m)
+    }
+
+    /// Whether both modes have the same resolution and refresh rate.
+    pub fn same_mode(&self, other: &Mode) -> bool {
+        self.width == other.width && self.height == other.height && self.refresh == other.refresh
+    }
+
+    /// The sway output sub-command which selects this mode.
+    fn command(&self) -> String {
+        match (&self.modeline, self.custom) {
+            (Some(ml), _) => format!("modeline {}", ml),
+            (None, true) => format!("mode --custom {}", self.resolution()),
+            (None, false) => format!("resolution {}", self.resolution()),
+        }
+    }
+
+    fn resolution(&self) -> String {
+        if self.refresh > 0 {
+            format!(
+                "{}x{}@{:.2}Hz",
+                self.width,
+                self.height,
+                self.refresh as f64 / 1000.
+            )
+        } else {
+            format!("{}x{}", self.width, self.height)
+        }
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = ConfigParseError;
+
+    /// Parses a mode in the form sway accepts for `resolution`, i.e.
+    /// `<width>x<height>[@<refresh>[Hz]]`. A missing refresh rate is
+    /// represented as 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ConfigParseError(format!("invalid mode {:?}", s));
+
+        let (res, refresh) = match s.split_once('@') {
+            Some((res, refresh)) => (res, Some(refresh.trim_end_matches("Hz"))),
+            None => (s, None),
+        };
+        let (width, height) = res.split_once('x').ok_or_else(err)?;
+
+        Ok(Mode {
+            width: width.parse().map_err(|_| err())?,
+            height: height.parse().map_err(|_| err())?,
+            refresh: match refresh {
+                Some(r) => (r.parse::<f64>().map_err(|_| err())? * 1000.).round() as i32,
+                None => 0,
+            },
+            ..Mode::default()
+        })
+    }
 }
 
 impl From<swayipc::Mode> for Mode {
@@ -111,11 +649,18 @@ impl From<swayipc::Mode> for Mode {
             width: m.width,
             height: m.height,
             refresh: m.refresh,
-            selected: false,
+            ..Mode::default()
         }
     }
 }
 
+/// A scale giving a whole number logical size, and that size.
+#[derive(Clone, Data, Lens)]
+pub struct ScaleSuggestion {
+    pub scale: Scale,
+    pub size: (u32, u32),
+}
+
 #[derive(Clone, Default, Data, Lens)]
 pub struct Display {
     pub name: String,
@@ -127,23 +672,115 @@ pub struct Display {
     pub position: Pos,
     pub size: (u32, u32),
     pub scale: Scale,
+    /// Whether the scale was configured or edited. Otherwise it is left to
+    /// sway to pick, and not written to the config.
+    pub scale_set: bool,
     pub transform: Transform,
 
+    pub adaptive_sync: bool,
+    pub subpixel: Subpixel,
+    pub render_bit_depth: RenderBitDepth,
+    pub max_render_time: MaxRenderTime,
+    pub allow_tearing: bool,
+
+    pub background: Background,
+
     pub modes: Vector<Mode>,
 
+    /// Whether the config refers to the display by make, model and serial
+    /// rather than by connector.
+    pub use_identifier: bool,
+
+    /// Sub-commands from the config file which are not modelled above,
+    /// kept verbatim so they survive a rewrite of the config.
+    pub extra: Vector<String>,
+
     pub id: Option<i64>,
 
     pub focused: bool,
+    /// Whether the display has edits which are not applied, or settings
+    /// which are not saved. Only set on the copies shown by `MonitorView`.
+    pub unapplied: bool,
+    pub unsaved: bool,
 }
 
 impl Display {
-    fn config(&self) -> String {
+    /// The make, model and serial of the display, as used by sway to
+    /// identify an output independently of its connector.
+    pub fn identifier(&self) -> String {
+        format!("{} {} {}", self.make, self.model, self.serial)
+    }
+
+    /// The name the display is written to the config as.
+    pub fn config_name(&self) -> String {
+        if self.use_identifier && !self.make.is_empty() {
+            self.identifier()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// Whether a config entry by the given name refers to this display.
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.name == name || self.identifier() == name
+    }
+
+    /// Scales between 1 and 3 at which the selected mode has a whole
+    /// number logical size, with that size.
+    pub fn scale_suggestions(&self) -> Vector<ScaleSuggestion> {
+        let mode = match self.modes.iter().find(|m| m.selected) {
+            Some(m) if m.width > 0 && m.height > 0 => m,
+            _ => return Vector::new(),
+        };
+        let (w, h) = (mode.width as u32, mode.height as u32);
+
+        (SCALE_STEPS..=3 * SCALE_STEPS)
+            .filter(|k| (w * SCALE_STEPS) % k == 0 && (h * SCALE_STEPS) % k == 0)
+            .map(|k| {
+                let (lw, lh) = (w * SCALE_STEPS / k, h * SCALE_STEPS / k);
+                ScaleSuggestion {
+                    scale: Scale(k as f64 / SCALE_STEPS as f64),
+                    size: if self.transform.quarter_turns() % 2 == 1 {
+                        (lh, lw)
+                    } else {
+                        (lw, lh)
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// The size of the display in the global (logical) coordinate space,
+    /// derived from the selected mode, scale and transform. Falls back to
+    /// the size last reported by sway if no mode is selected.
+    pub fn logical_size(&self) -> (u32, u32) {
+        let mode = match self.modes.iter().find(|m| m.selected) {
+            Some(m) => m,
+            None => return self.size,
+        };
+
+        let (w, h) = if self.transform.quarter_turns() % 2 == 1 {
+            (mode.height, mode.width)
+        } else {
+            (mode.width, mode.height)
+        };
+        let scale = if self.scale.0 > 0. { self.scale.0 } else { 1. };
+
+        (
+            (w as f64 / scale).round() as u32,
+            (h as f64 / scale).round() as u32,
+        )
+    }
+
+    pub(crate) fn config(&self) -> String {
         let mut line = String::with_capacity(200);
         line.push_str("output ");
-        line.push_str(&self.name);
+        line.push_str(&quote_arg(&self.config_name()));
 
-        line.push_str(" scale ");
-        line.push_str(format!("{:.2}", self.scale.0).as_str());
+        if self.scale_set {
+            line.push_str(" scale ");
+            line.push_str(&self.scale.command());
+        }
 
         if self.position != (0, 0).into() {
             line.push_str(" pos ");
@@ -157,165 +794,1566 @@ impl Display {
 
         for m in self.modes.iter() {
             if m.selected {
-                line.push_str(" resolution ");
-                line.push_str(
-                    format!("{}x{}@{:.2}Hz", m.width, m.height, m.refresh as f64 / 1000.).as_str(),
-                );
+                line.push(' ');
+                line.push_str(&m.command());
             }
         }
 
-        line
-    }
-}
+        if self.adaptive_sync {
+            line.push_str(" adaptive_sync on");
+        }
+        if self.subpixel != Subpixel::Unknown {
+            line.push_str(" subpixel ");
+            line.push_str(format!("{}", self.subpixel).as_str());
+        }
+        if self.render_bit_depth != RenderBitDepth::Eight {
+            line.push_str(" render_bit_depth ");
+            line.push_str(format!("{}", self.render_bit_depth).as_str());
+        }
+        if self.max_render_time != MaxRenderTime(0) {
+            line.push_str(" max_render_time ");
+            line.push_str(format!("{}", self.max_render_time).as_str());
+        }
+        if self.allow_tearing {
+            line.push_str(" allow_tearing yes");
+        }
+        if self.background.is_set() {
+            line.push(' ');
+            line.push_str(&self.background.command());
+        }
 
-impl From<Output> for Display {
-    fn from(o: Output) -> Self {
-        let mut modes: Vector<Mode> = o.modes.into_iter().map(|m| m.into()).collect();
-        if let Some(cm) = o.current_mode {
-            let cm = cm.into();
-            for m in modes.iter_mut() {
-                if m.same(&cm) {
-                    m.selected = true;
-                    break;
-                }
-            }
+        for e in self.extra.iter() {
+            line.push(' ');
+            line.push_str(e);
         }
 
-        Display {
-            name: o.name,
-            make: o.make,
-            model: o.model,
-            serial: o.serial,
-            active: o.active,
+        if !self.active {
+            line.push_str(" disable");
+        }
 
-            position: (o.rect.x, o.rect.y).into(),
-            size: (o.rect.width as u32, o.rect.height as u32),
-            scale: o.scale.unwrap_or(1.).into(),
-            transform: match o.transform {
-                Some(t) => match t.as_str() {
-                    "90" => Transform::R90,
-                    "180" => Transform::R180,
-                    "270" => Transform::R270,
-                    _ => Transform::None,
-                },
-                _ => Transform::None,
-            },
+        line
+    }
 
-            modes,
-            id: o.id,
-            focused: false,
+    /// The sway command which changes the live display into this one, if
+    /// they differ.
+    pub fn apply_command(&self, live: &Display) -> Option<String> {
+        let mut cmd = String::with_capacity(200);
+        cmd.push_str("output ");
+        cmd.push_str(&quote_arg(&live.name));
+        let base_len = cmd.len();
+
+        if self.active != live.active {
+            cmd.push_str(if self.active { " enable" } else { " disable" });
         }
-    }
-}
 
-#[derive(Clone, Default, Data, Lens)]
-pub struct App {
-    pub display_geo: HashMap<String, Display>,
-}
+        if !self.scale.same(&live.scale) {
+            cmd.push_str(" scale ");
+            cmd.push_str(&self.scale.command());
+        }
 
-impl From<Vec<Output>> for App {
-    fn from(outputs: Vec<Output>) -> Self {
-        let mut display_geo = HashMap::new();
-        for o in outputs {
-            display_geo.insert(o.name.clone(), o.into());
+        if !self.position.same(&live.position) {
+            cmd.push_str(" pos ");
+            cmd.push_str(format!("{} {}", self.position.0, self.position.1).as_str());
         }
 
-        App { display_geo }
-    }
-}
+        if !self.transform.same(&live.transform) {
+            cmd.push_str(" transform ");
+            cmd.push_str(format!("{}", self.transform).as_str());
+        }
 
-impl App {
-    pub fn save_config(&self, mut base_path: PathBuf) -> Result<(), std::io::Error> {
-        use std::fs::OpenOptions;
-        use std::io::{prelude::*, BufReader};
+        if self.adaptive_sync != live.adaptive_sync {
+            cmd.push_str(if self.adaptive_sync {
+                " adaptive_sync on"
+            } else {
+                " adaptive_sync off"
+            });
+        }
 
-        base_path.push("displays");
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&base_path)?;
-
-        let mut existing_lines: HashMap<String, String> = HashMap::new();
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = &line?;
-            let line = line.trim();
-            if line.starts_with("#") {
-                continue;
-            }
-            let spl: Vec<_> = line.clone().split(" ").collect();
-            if spl.len() > 3 && spl[0] == "output" && !self.display_geo.contains_key(spl[1]) {
-                existing_lines.insert(spl[1].into(), line.into());
-            }
+        if self.subpixel != live.subpixel {
+            cmd.push_str(" subpixel ");
+            cmd.push_str(format!("{}", self.subpixel).as_str());
         }
 
-        let mut file = OpenOptions::new()
-            .read(false)
-            .write(true)
-            .truncate(true)
-            .open(&base_path)?;
+        if self.render_bit_depth != live.render_bit_depth {
+            cmd.push_str(" render_bit_depth ");
+            cmd.push_str(format!("{}", self.render_bit_depth).as_str());
+        }
 
-        write!(file, "# Automatically generated - do not edit!!\n\n")?;
-        for (_, l) in existing_lines {
-            write!(file, "{}\n", l)?;
+        if self.max_render_time != live.max_render_time {
+            cmd.push_str(" max_render_time ");
+            cmd.push_str(format!("{}", self.max_render_time).as_str());
         }
-        for (_, d) in self.display_geo.iter() {
-            write!(file, "{}\n", d.config())?;
+
+        if self.allow_tearing != live.allow_tearing {
+            cmd.push_str(if self.allow_tearing {
+                " allow_tearing yes"
+            } else {
+                " allow_tearing no"
+            });
         }
 
-        Ok(())
+        if self.background.is_set()
+            && (self.background.source != live.background.source
+                || self.background.mode != live.background.mode)
+        {
+            cmd.push(' ');
+            cmd.push_str(&self.background.command());
+        }
+
+        let live_mode = live.modes.iter().find(|m| m.selected);
+        for m in self.modes.iter() {
+            if m.selected && !live_mode.map_or(false, |l| l.same_mode(m)) {
+                cmd.push(' ');
+                cmd.push_str(&m.command());
+            }
+        }
+
+        if cmd.len() > base_len {
+            Some(cmd)
+        } else {
+            None
+        }
     }
 
-    pub fn apply_displays(&mut self) {
-        let mut conn = swayipc::Connection::new().unwrap();
-        let mut outputs = conn.get_outputs().unwrap();
-        for o in outputs {
-            if let Some(our) = self.display_geo.get(&o.name) {
-                let live = o.into();
-                if !our.same(&live) {
-                    let mut cmd = String::with_capacity(200);
-                    cmd.push_str("output ");
-                    cmd.push_str(&live.name);
-
-                    if !our.scale.same(&live.scale) {
-                        cmd.push_str(" scale ");
-                        cmd.push_str(format!("{:.2}", our.scale.0).as_str());
-                    }
+    /// Whether the user-editable settings of both displays are the same,
+    /// ignoring state such as focus or the list of available modes.
+    pub fn same_settings(&self, other: &Display) -> bool {
+        self.active == other.active
+            && self.position.same(&other.position)
+            && self.scale.same(&other.scale)
+            && self.transform.same(&other.transform)
+            && self.adaptive_sync == other.adaptive_sync
+            && self.subpixel == other.subpixel
+            && self.render_bit_depth == other.render_bit_depth
+            && self.max_render_time == other.max_render_time
+            && self.allow_tearing == other.allow_tearing
+            && self.background == other.background
+            && self.extra.same(&other.extra)
+            && match (
+                self.modes.iter().find(|m| m.selected),
+                other.modes.iter().find(|m| m.selected),
+            ) {
+                (Some(a), Some(b)) => a.same_mode(b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
 
-                    if !our.position.same(&live.position) {
-                        cmd.push_str(" pos ");
-                        cmd.push_str(format!("{} {}", our.position.0, our.position.1).as_str());
-                    }
+    /// The names of the user-editable settings which differ between the
+    /// displays.
+    pub fn changed_settings(&self, other: &Display) -> Vec<&'static str> {
+        let mode = |d: &Display| d.modes.iter().find(|m| m.selected).cloned();
+        let same_mode = match (mode(self), mode(other)) {
+            (Some(a), Some(b)) => a.same_mode(&b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
 
-                    if !our.transform.same(&live.transform) {
-                        cmd.push_str(" transform ");
-                        cmd.push_str(format!("{}", our.transform).as_str());
-                    }
+        [
+            ("enabled", self.active == other.active),
+            ("position", self.position.same(&other.position)),
+            ("scale", self.scale.same(&other.scale)),
+            ("transform", self.transform.same(&other.transform)),
+            ("mode", same_mode),
+            ("adaptive_sync", self.adaptive_sync == other.adaptive_sync),
+            ("subpixel", self.subpixel == other.subpixel),
+            (
+                "render_bit_depth",
+                self.render_bit_depth == other.render_bit_depth,
+            ),
+            (
+                "max_render_time",
+                self.max_render_time == other.max_render_time,
+            ),
+            ("allow_tearing", self.allow_tearing == other.allow_tearing),
+            ("background", self.background == other.background),
+            ("extra", self.extra.same(&other.extra)),
+        ]
+        .iter()
+        .filter(|(_, same)| !same)
+        .map(|(name, _)| *name)
+        .collect()
+    }
+
+    /// Copies the settings which sway does not report over IPC, including
+    /// the options we don't model, so that they are neither mistaken for
+    /// changes nor dropped from the config.
+    fn keep_unreported(&mut self, from: &Display) {
+        self.adaptive_sync = from.adaptive_sync;
+        self.render_bit_depth = from.render_bit_depth;
+        self.max_render_time = from.max_render_time;
+        self.allow_tearing = from.allow_tearing;
+        self.background = from.background.clone();
+        self.extra = from.extra.clone();
+        self.use_identifier = from.use_identifier;
+        self.scale_set = from.scale_set;
+    }
+
+    /// Overlays the settings of a display read from the config file on top
+    /// of this (live) display.
+    pub fn apply_saved(&mut self, saved: &Display) {
+        self.active = saved.active;
+        self.position = saved.position;
+        if saved.scale_set {
+            self.scale = saved.scale;
+            self.scale_set = true;
+        }
+        self.transform = saved.transform;
+        self.adaptive_sync = saved.adaptive_sync;
+        self.subpixel = saved.subpixel;
+        self.render_bit_depth = saved.render_bit_depth;
+        self.max_render_time = saved.max_render_time;
+        self.allow_tearing = saved.allow_tearing;
+        self.background = saved.background.clone();
+        self.extra = saved.extra.clone();
+
+        for m in saved.modes.iter().filter(|m| m.custom) {
+            if !self.modes.iter().any(|o| o.same_mode(m)) {
+                let mut m = m.clone();
+                m.selected = false;
+                self.modes.push_back(m);
+            }
+        }
+
+        if let Some(want) = saved.modes.iter().find(|m| m.selected) {
+            self.select_mode(want);
+        }
+    }
+
+    /// Selects the available mode closest to `want`, preferring the highest
+    /// refresh rate if it doesn't specify one. Returns false if there is no
+    /// mode of that resolution.
+    pub fn select_mode(&mut self, want: &Mode) -> bool {
+        let best = self
+            .modes
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.width == want.width && m.height == want.height)
+            .filter(|(_, m)| !want.custom || m.same_mode(want))
+            .min_by_key(|(_, m)| {
+                if want.refresh > 0 {
+                    (m.refresh - want.refresh).abs()
+                } else {
+                    -m.refresh
+                }
+            })
+            .map(|(i, _)| i);
+
+        match best {
+            Some(best) => {
+                for (i, m) in self.modes.iter_mut().enumerate() {
+                    m.selected = i == best;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigParseError(pub String);
 
-                    for m in our.modes.iter() {
-                        if m.selected {
-                            cmd.push_str(" resolution ");
-                            cmd.push_str(
-                                format!(
-                                    "{}x{}@{:.2}Hz",
-                                    m.width,
-                                    m.height,
-                                    m.refresh as f64 / 1000.
-                                )
-                                .as_str(),
-                            );
+impl std::fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigParseError {}
+
+/// Output sub-commands understood by sway, used to find where the arguments
+/// of a sub-command we don't model end.
+const OUTPUT_SUBCOMMANDS: &[&str] = &[
+    "adaptive_sync",
+    "allow_tearing",
+    "background",
+    "bg",
+    "disable",
+    "dpms",
+    "enable",
+    "max_render_time",
+    "mode",
+    "modeline",
+    "pos",
+    "position",
+    "power",
+    "render_bit_depth",
+    "res",
+    "resolution",
+    "scale",
+    "scale_filter",
+    "subpixel",
+    "toggle",
+    "transform",
+];
+
+/// Splits a config line into arguments, honouring double quotes.
+pub(crate) fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+pub(crate) fn quote_arg(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(char::is_whitespace) {
+        format!("\"{}\"", arg)
+    } else {
+        arg.to_string()
+    }
+}
+
+impl std::str::FromStr for Display {
+    type Err = ConfigParseError;
+
+    /// Parses a single `output <name> <sub-command>...` directive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = split_args(s);
+        if args.len() < 2 || args[0] != "output" {
+            return Err(ConfigParseError(format!("not an output directive: {:?}", s)));
+        }
+
+        let mut d = Display {
+            name: args[1].clone(),
+            active: true,
+            scale: Scale(1.),
+            ..Display::default()
+        };
+
+        let missing = |cmd: &str| ConfigParseError(format!("missing argument to {}", cmd));
+        let mut rest = args[2..].iter().peekable();
+        while let Some(cmd) = rest.next() {
+            match cmd.as_str() {
+                "scale" => {
+                    let v = rest.next().ok_or_else(|| missing(cmd))?;
+                    d.scale = v.parse()?;
+                    d.scale_set = true;
+                }
+                "pos" | "position" => {
+                    let (x, y) = (
+                        rest.next().ok_or_else(|| missing(cmd))?,
+                        rest.next().ok_or_else(|| missing(cmd))?,
+                    );
+                    d.position = format!("{},{}", x, y)
+                        .parse()
+                        .map_err(|_| ConfigParseError(format!("invalid position {} {}", x, y)))?;
+                }
+                "transform" => {
+                    let t = rest.next().ok_or_else(|| missing(cmd))?;
+                    match rest.peek().map(|s| s.as_str()) {
+                        // Relative transforms depend on the live state, so
+                        // keep them as-is instead of modelling them.
+                        Some("clockwise") | Some("anticlockwise") => {
+                            d.extra
+                                .push_back(format!("transform {} {}", t, rest.next().unwrap()));
                         }
+                        _ => d.transform = t.parse()?,
                     }
+                }
+                "adaptive_sync" => {
+                    d.adaptive_sync = parse_toggle(rest.next().ok_or_else(|| missing(cmd))?)?;
+                }
+                "subpixel" => {
+                    d.subpixel = rest.next().ok_or_else(|| missing(cmd))?.parse()?;
+                }
+                "render_bit_depth" => {
+                    d.render_bit_depth = rest.next().ok_or_else(|| missing(cmd))?.parse()?;
+                }
+                "max_render_time" => {
+                    let v = rest.next().ok_or_else(|| missing(cmd))?;
+                    d.max_render_time = v.parse().map_err(|_| {
+                        ConfigParseError(format!("invalid max_render_time {:?}", v))
+                    })?;
+                }
+                "allow_tearing" => {
+                    d.allow_tearing = parse_toggle(rest.next().ok_or_else(|| missing(cmd))?)?;
+                }
+                "bg" | "background" => {
+                    let (source, mode) = (
+                        rest.next().ok_or_else(|| missing(cmd))?,
+                        rest.next().ok_or_else(|| missing(cmd))?,
+                    );
+                    match rest.peek() {
+                        // A fallback colour isn't modelled, so keep the
+                        // whole background as-is.
+                        Some(c) if c.starts_with('#') => d.extra.push_back(format!(
+                            "bg {} {} {}",
+                            quote_arg(source),
+                            mode,
+                            rest.next().unwrap()
+                        )),
+                        _ => {
+                            d.background.source = source.clone();
+                            d.background.mode = mode.parse()?;
+                        }
+                    }
+                }
+                "enable" => d.active = true,
+                "disable" => d.active = false,
+                "resolution" | "res" | "mode" => {
+                    let mut arg = rest.next().ok_or_else(|| missing(cmd))?;
+                    let custom = arg == "--custom";
+                    if custom {
+                        arg = rest.next().ok_or_else(|| missing(cmd))?;
+                    }
+                    let mut m: Mode = arg.parse()?;
+                    m.selected = true;
+                    m.custom = custom;
+                    d.modes = Vector::unit(m);
+                }
+                "modeline" => {
+                    let mut args = Vec::with_capacity(11);
+                    for _ in 0..11 {
+                        args.push(quote_arg(rest.next().ok_or_else(|| missing(cmd))?));
+                    }
+                    let ml: Modeline = args.join(" ").parse()?;
+                    d.modes = Vector::unit(Mode {
+                        width: ml.hdisplay,
+                        height: ml.vdisplay,
+                        refresh: ml.refresh(),
+                        selected: true,
+                        custom: true,
+                        modeline: Some(ml),
+                    });
+                }
+                _ => {
+                    let mut e = cmd.clone();
+                    while let Some(arg) = rest.peek() {
+                        if OUTPUT_SUBCOMMANDS.contains(&arg.as_str()) {
+                            break;
+                        }
+                        e.push(' ');
+                        e.push_str(&quote_arg(rest.next().unwrap()));
+                    }
+                    d.extra.push_back(e);
+                }
+            }
+        }
+
+        Ok(d)
+    }
+}
+
+/// Parses the `output` directives of a config file, ignoring comments and
+/// any other directives.
+pub fn parse_config(contents: &str) -> Result<Vec<Display>, ConfigParseError> {
+    display_lines(contents).map(|l| l.parse()).collect()
+}
+
+/// The `output` lines of a config which configure a single display, rather
+/// than all of them with `output *`.
+fn display_lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| l.starts_with("output ") && split_args(l).get(1).map_or(false, |n| n != "*"))
+}
+
+impl From<Output> for Display {
+    fn from(o: Output) -> Self {
+        let mut modes: Vector<Mode> = o.modes.into_iter().map(|m| m.into()).collect();
+        if let Some(cm) = o.current_mode {
+            let mut cm: Mode = cm.into();
+            match modes.iter_mut().find(|m| m.same_mode(&cm)) {
+                Some(m) => m.selected = true,
+                // The current mode isn't advertised, so it must be custom.
+                None => {
+                    cm.selected = true;
+                    cm.custom = true;
+                    modes.push_back(cm);
+                }
+            }
+        }
+
+        // Disabled outputs have no geometry, so size them by their
+        // preferred mode.
+        let size = match (o.rect.width, o.rect.height, modes.front()) {
+            (0, 0, Some(m)) => (m.width as u32, m.height as u32),
+            (w, h, _) => (w as u32, h as u32),
+        };
+
+        Display {
+            name: o.name,
+            make: o.make,
+            model: o.model,
+            serial: o.serial,
+            active: o.active,
+
+            position: (o.rect.x, o.rect.y).into(),
+            size,
+            scale: o.scale.unwrap_or(1.).into(),
+            scale_set: false,
+            transform: o.transform.and_then(|t| t.parse().ok()).unwrap_or_default(),
+
+            adaptive_sync: false,
+            subpixel: o
+                .subpixel_hinting
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default(),
+            render_bit_depth: RenderBitDepth::Eight,
+            max_render_time: MaxRenderTime(0),
+            allow_tearing: false,
+            background: Background::default(),
+
+            modes,
+            use_identifier: false,
+            extra: Vector::new(),
+            id: o.id,
+            focused: false,
+            unapplied: false,
+            unsaved: false,
+        }
+    }
+}
+
+const CONFIG_HEADER: &str = "# Display settings written by twl-configurator. Output lines are\n\
+                             # rewritten on save, comments and other lines are kept.";
+
+/// The output configured by a line of the displays file which we manage.
+/// Lines we can't read back are kept as they are.
+fn managed_output(line: &str) -> Option<String> {
+    if let Some(rotation) = RotateOpts::from_config(line) {
+        return Some(rotation.output);
+    }
+    let line = display_lines(line).next()?;
+    line.parse::<Display>().ok().map(|d| d.name)
+}
+
+/// The lines of the displays file for a display, keyed by the name it is
+/// configured under.
+fn config_block(d: &Display) -> (String, Vec<String>) {
+    let name = d.config_name();
+    let mut lines = vec![d.config()];
+    if let Some(rotation) = RotateOpts::from_background(&name, &d.background) {
+        lines.push(rotation.config());
+    }
+    (name, lines)
+}
+
+/// What Apply and Save would do to a display.
+#[derive(Clone, Data, Lens)]
+pub struct PendingChange {
+    pub name: String,
+    /// The sway command Apply would run, if any.
+    pub command: String,
+    /// The config lines Save would write, if they differ from the file.
+    pub config: String,
+}
+
+/// How long the user has to confirm newly applied display settings before
+/// they are reverted.
+pub const REVERT_TIMEOUT_SECS: u32 = 15;
+
+/// The display state from before settings were applied, kept until the user
+/// confirms the new settings.
+#[derive(Clone, Data, Lens)]
+pub struct PendingRevert {
+    pub previous: HashMap<String, Display>,
+    pub seconds_left: u32,
+}
+
+#[derive(Clone, Default, Data, Lens)]
+pub struct App {
+    pub display_geo: HashMap<String, Display>,
+    /// Displays as described by the config file, including those which
+    /// are not currently connected.
+    pub saved: HashMap<String, Display>,
+    /// Displays as last reported by sway.
+    pub live: HashMap<String, Display>,
+
+    pub pending_revert: Option<PendingRevert>,
+    pub show_review: bool,
+    /// Earlier layouts of `display_geo`, for undo and redo.
+    pub history: History,
+    pub show_advanced: bool,
+    /// Custom mode or modeline being entered, and why it was rejected.
+    pub custom_mode: String,
+    pub custom_mode_error: String,
+    /// Why the scale being entered is rejected.
+    pub scale_error: String,
+    pub show_scale_suggestions: bool,
+
+    pub profiles: Vector<Profile>,
+    /// Name entered for saving the current layout as a profile.
+    pub profile_name: String,
+
+    pub show_background: bool,
+    /// Whether to refer to all displays by make, model and serial in the
+    /// config.
+    pub use_identifiers: bool,
+    /// Images in the wallpaper directory.
+    pub wallpapers: Vector<String>,
+
+    pub show_workspaces: bool,
+    /// Workspaces and the outputs they are placed on.
+    pub workspaces: Vector<Workspace>,
+
+    /// Screen and keyboard backlights, tied to outputs by connector.
+    pub backlights: Vector<Backlight>,
+
+    pub show_night_light: bool,
+    pub night_light: NightLight,
+
+    pub notifications: Notifications,
+}
+
+impl From<Vec<Display>> for App {
+    fn from(outputs: Vec<Display>) -> Self {
+        let mut display_geo = HashMap::new();
+        for o in outputs {
+            display_geo.insert(o.name.clone(), o);
+        }
+
+        App {
+            live: display_geo.clone(),
+            display_geo,
+            saved: HashMap::new(),
+            pending_revert: None,
+            show_review: false,
+            history: History::default(),
+            show_advanced: false,
+            custom_mode: String::new(),
+            custom_mode_error: String::new(),
+            scale_error: String::new(),
+            show_scale_suggestions: false,
+            profiles: Vector::new(),
+            profile_name: String::new(),
+            show_background: false,
+            use_identifiers: false,
+            wallpapers: Vector::new(),
+            show_workspaces: false,
+            workspaces: Vector::new(),
+            backlights: Vector::new(),
+            show_night_light: false,
+            night_light: NightLight::default(),
+            notifications: Notifications::default(),
+        }
+    }
+}
+
+impl App {
+    /// Reads the displays config file, and overlays the settings it
+    /// describes on top of the connected displays.
+    pub fn load_config(&mut self, mut base_path: PathBuf) -> Result<(), std::io::Error> {
+        base_path.push("displays");
+        let contents = match std::fs::read_to_string(&base_path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        // Lines which don't parse are reported, but the rest still load, as
+        // saving keeps those lines as they are.
+        let mut invalid = None;
+        let mut saved: Vec<Display> = display_lines(&contents)
+            .filter_map(|l| match l.parse() {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    invalid.get_or_insert(e);
+                    None
+                }
+            })
+            .collect();
+        for rotation in contents.lines().filter_map(RotateOpts::from_config) {
+            if let Some(s) = saved.iter_mut().find(|s| s.name == rotation.output) {
+                s.background.rotate_minutes = rotation.interval;
+            }
+        }
+
+        self.saved = HashMap::new();
+        for s in saved {
+            let connected = self
+                .display_geo
+                .iter_mut()
+                .find(|(_, d)| d.matches_name(&s.name));
+            if let Some((name, d)) = connected {
+                d.apply_saved(&s);
+                d.use_identifier = *name != s.name;
+                // Sway doesn't report these, so what was saved is our best
+                // guess at what it is using.
+                if let Some(live) = self.live.get_mut(name) {
+                    live.keep_unreported(&s);
+                    live.use_identifier = d.use_identifier;
+                }
+            }
+            self.saved.insert(s.name.clone(), s);
+        }
+
+        match invalid {
+            Some(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Merges a fresh list of outputs from sway into the model. Displays
+    /// with edits which have not been applied yet keep those edits.
+    ///
+    /// Returns true if the set of connected displays changed.
+    pub fn merge_outputs(&mut self, outputs: Vec<Display>) -> bool {
+        let mut fresh = App::from(outputs);
+        fresh.keep_unreported(&self.live);
+
+        let mut display_geo = HashMap::new();
+        for (name, mut d) in fresh.display_geo.into_iter() {
+            if let Some(ours) = self.display_geo.get(&name) {
+                let edited = self
+                    .live
+                    .get(&name)
+                    .map_or(false, |live| !ours.same_settings(live));
+                if edited {
+                    d.apply_saved(ours);
+                }
+                d.focused = ours.focused;
+                d.use_identifier = ours.use_identifier;
+            }
+            display_geo.insert(name, d);
+        }
+
+        let changed = display_geo.len() != self.display_geo.len()
+            || display_geo
+                .keys()
+                .any(|name| !self.display_geo.contains_key(name));
+
+        self.display_geo = display_geo;
+        self.live = fresh.live;
+        changed
+    }
+
+    fn keep_unreported(&mut self, from: &HashMap<String, Display>) {
+        for (name, d) in self.display_geo.iter_mut() {
+            if let Some(f) = from.get(name) {
+                d.keep_unreported(f);
+            }
+        }
+        for (name, d) in self.live.iter_mut() {
+            if let Some(f) = from.get(name) {
+                d.keep_unreported(f);
+            }
+        }
+    }
+
+    /// Adds the entered custom mode to the focused display and selects it.
+    pub fn add_custom_mode(&mut self) {
+        let mut mode = match Mode::parse_custom(&self.custom_mode) {
+            Ok(m) => m,
+            Err(e) => {
+                self.custom_mode_error = e.to_string();
+                return;
+            }
+        };
+        self.custom_mode_error.clear();
+
+        for (_, d) in self.display_geo.iter_mut() {
+            if d.focused {
+                d.modes.retain(|m| !(m.custom && m.same_mode(&mode)));
+                for m in d.modes.iter_mut() {
+                    m.selected = false;
+                }
+                mode.selected = true;
+                d.modes.push_back(mode);
+                self.custom_mode.clear();
+                return;
+            }
+        }
+
+        self.custom_mode_error = "Select a display first".into();
+    }
+
+    /// Reads the images available in the wallpaper directory.
+    pub fn load_wallpapers(&mut self, dir: &Path) -> Result<(), std::io::Error> {
+        let images = match wallpaper::list_images(dir) {
+            Ok(images) => images,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        self.wallpapers = images
+            .into_iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        Ok(())
+    }
+
+    /// Puts back the settings of the connected displays from a layout in
+    /// the history, leaving the focus where it is.
+    fn restore(&mut self, layout: HashMap<String, Display>) {
+        for (name, d) in self.display_geo.iter_mut() {
+            if let Some(saved) = layout.get(name) {
+                let focused = d.focused;
+                *d = saved.clone();
+                d.focused = focused;
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(layout) = self.history.undo(&self.display_geo) {
+            self.restore(layout);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(layout) = self.history.redo(&self.display_geo) {
+            self.restore(layout);
+        }
+    }
+
+    pub fn load_backlights(&mut self, sysfs_root: &Path) -> Result<(), std::io::Error> {
+        let connectors: Vec<String> = self.display_geo.keys().cloned().collect();
+        self.backlights = backlight::list(sysfs_root, &connectors)?;
+        Ok(())
+    }
+
+    /// The index of the backlight of a kind belonging to the focused display.
+    pub fn focused_backlight(&self, kind: backlight::Kind) -> Option<usize> {
+        let focused = self.display_geo.values().find(|d| d.focused)?;
+        self.backlights
+            .iter()
+            .position(|b| b.kind == kind && b.output.as_ref() == Some(&focused.name))
+    }
 
-                    println!("command = {:?}", cmd);
-                    conn.run_command(cmd);
+    pub fn load_workspaces(
+        &mut self,
+        base_path: &Path,
+        backend: &mut dyn OutputBackend,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut workspaces = workspace::load(base_path)?;
+        // Keep the saved rules even if the compositor can't be asked for its
+        // workspaces.
+        let current = backend.workspaces();
+        workspace::add_current(&mut workspaces, current.as_deref().unwrap_or_default());
+        self.workspaces = workspaces.into_iter().collect();
+        Ok(current.map(|_| ())?)
+    }
+
+    pub fn save_workspaces(&self, base_path: &Path) -> Result<(), std::io::Error> {
+        workspace::save(base_path, &self.workspaces)
+    }
+
+    /// Adds the focused display to the end of the outputs of a workspace,
+    /// by make, model and serial so the rule follows the monitor.
+    pub fn add_workspace_output(&mut self, name: &str) {
+        let output = match self.display_geo.values().find(|d| d.focused) {
+            Some(d) => d.identifier(),
+            None => return,
+        };
+        if let Some(w) = self.workspaces.iter_mut().find(|w| w.name == name) {
+            if !w.outputs.0.contains(&output) {
+                w.outputs.0.push_back(output);
+            }
+        }
+    }
+
+    pub fn load_profiles(&mut self, base_path: PathBuf) -> Result<(), std::io::Error> {
+        self.profiles = Profile::load_all(base_path)?;
+        Ok(())
+    }
+
+    /// Saves the current layout as a profile named after `profile_name`,
+    /// replacing any existing profile of that name.
+    pub fn save_profile(&mut self, base_path: PathBuf) -> Result<(), std::io::Error> {
+        let profile = Profile::from_displays(self.profile_name.clone(), &self.display_geo);
+        profile.save(base_path)?;
+
+        self.profiles.retain(|p| p.name != profile.name);
+        self.profiles.push_back(profile);
+        self.profiles
+            .sort_by(|a: &Profile, b: &Profile| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    pub fn apply_profile(&mut self, name: &str) {
+        if let Some(p) = self.profiles.iter().find(|p| p.name == name) {
+            p.apply(&mut self.display_geo);
+        }
+    }
+
+    /// Applies the first profile describing exactly the connected displays,
+    /// returning its name.
+    pub fn apply_matching_profile(&mut self, backend: &mut dyn OutputBackend) -> Option<String> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|p| p.matches(&self.display_geo))?
+            .clone();
+
+        profile.apply(&mut self.display_geo);
+        if let Err(e) = self.apply_displays(backend) {
+            self.notifications
+                .error(&format!("Failed to apply profile {:?}", profile.name), e);
+        }
+        Some(profile.name)
+    }
+
+    /// Writes the displays to the config file, replacing the output lines
+    /// and keeping everything else in it.
+    pub fn save_config(&self, mut base_path: PathBuf) -> Result<(), std::io::Error> {
+        base_path.push("displays");
+
+        let mut displays: Vec<Display> = self
+            .saved
+            .iter()
+            .filter(|(n, _)| !self.display_geo.values().any(|d| d.matches_name(n)))
+            .map(|(_, d)| d.clone())
+            .chain(self.display_geo.values().map(|d| self.as_saved(d)))
+            .collect();
+        displays.sort_by_key(|d| d.config_name());
+
+        let blocks: Vec<(String, Vec<String>)> = displays.iter().map(config_block).collect();
+
+        let contents = config::merge(
+            &config::read(&base_path)?,
+            CONFIG_HEADER,
+            &blocks,
+            managed_output,
+        );
+        config::write_atomic(&base_path, &contents)
+    }
+
+    /// Applies the displays, keeping the previous state around so it can be
+    /// restored if the user does not confirm the new settings in time.
+    ///
+    /// Applying again before confirming keeps the state from before the
+    /// first apply, which is the last one known to work, and restarts the
+    /// countdown.
+    pub fn apply_displays_with_confirm(
+        &mut self,
+        backend: &mut dyn OutputBackend,
+    ) -> Result<(), BackendError> {
+        let previous = match self.pending_revert.take() {
+            Some(revert) => revert.previous,
+            None => self.live.clone(),
+        };
+        let result = self.apply_displays(backend);
+
+        let changed = previous.len() != self.live.len()
+            || previous
+                .iter()
+                .any(|(n, d)| self.live.get(n).map_or(true, |live| !live.same_settings(d)));
+        if changed {
+            self.pending_revert = Some(PendingRevert {
+                previous,
+                seconds_left: REVERT_TIMEOUT_SECS,
+            });
+        }
+        result
+    }
+
+    pub fn keep_displays(&mut self) {
+        self.pending_revert = None;
+    }
+
+    /// Restores the display state from before the last apply.
+    pub fn revert_displays(&mut self, backend: &mut dyn OutputBackend) -> Result<(), BackendError> {
+        match self.pending_revert.take() {
+            Some(revert) => {
+                self.display_geo = revert.previous;
+                self.apply_displays(backend)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Called once the last window is closed. Settings still waiting to be
+    /// confirmed were never confirmed, so they are reverted.
+    pub fn on_exit(&mut self, backend: &mut dyn OutputBackend) -> Result<(), BackendError> {
+        self.revert_displays(backend)
+    }
+
+    pub fn apply_displays(&mut self, backend: &mut dyn OutputBackend) -> Result<(), BackendError> {
+        // A scale the user picked stays theirs once applied, rather than
+        // being left to sway again.
+        let live = &self.live;
+        for (name, d) in self.display_geo.iter_mut() {
+            d.scale_set |= live.get(name).map_or(false, |l| !l.scale.same(&d.scale));
+        }
+
+        let mut changes = Vec::new();
+        for mut live in backend.outputs()? {
+            if let Some(our) = self.display_geo.get(&live.name) {
+                if let Some(l) = self.live.get(&live.name) {
+                    live.keep_unreported(l);
+                }
+                if our.background != live.background {
+                    // Keyed like the exec_always line Save writes, so that
+                    // this replaces the rotation started from the config.
+                    let output = self.as_saved(our).config_name();
+                    if let Err(e) = wallpaper::update_rotation(&output, &our.background) {
+                        self.notifications
+                            .error("Failed to update the wallpaper rotation", e);
+                    }
                 }
+                changes.push(Change {
+                    live,
+                    want: our.clone(),
+                });
             }
         }
+        backend.apply(&changes)?;
 
         // Update ourselves based on the new reality of things
-        outputs = conn.get_outputs().unwrap();
-        *self = outputs.into();
+        let mut fresh = App::from(backend.outputs()?);
+        fresh.keep_unreported(&self.display_geo);
+        self.display_geo = fresh.display_geo;
+        self.live = fresh.live;
+        Ok(())
+    }
+
+    /// A connected display as Save writes it, named by its identifier if
+    /// either it or the global setting asks for that.
+    fn as_saved(&self, d: &Display) -> Display {
+        let mut d = d.clone();
+        d.use_identifier |= self.use_identifiers;
+        d.scale_set |= self
+            .live
+            .get(&d.name)
+            .map_or(false, |live| !live.scale.same(&d.scale));
+        d
+    }
+
+    /// The config lines Save would write for a connected display.
+    fn config_lines(&self, d: &Display) -> Vec<String> {
+        config_block(&self.as_saved(d)).1
+    }
+
+    /// Settings of a connected display which were edited but not applied.
+    pub fn unapplied_settings(&self, d: &Display) -> Vec<&'static str> {
+        self.live
+            .get(&d.name)
+            .map_or(Vec::new(), |live| d.changed_settings(live))
+    }
+
+    /// Whether saving would change the lines of a connected display in the
+    /// displays file.
+    pub fn is_unsaved(&self, d: &Display) -> bool {
+        match self.saved.iter().find(|(n, _)| d.matches_name(n)) {
+            Some((_, saved)) => self.config_lines(d) != config_block(saved).1,
+            None => true,
+        }
+    }
+
+    /// The connected displays, marked with whether they have unapplied or
+    /// unsaved changes.
+    pub fn marked_displays(&self) -> HashMap<String, Display> {
+        let mut displays = self.display_geo.clone();
+        for (_, d) in displays.iter_mut() {
+            d.unapplied = !self.unapplied_settings(d).is_empty();
+            d.unsaved = self.is_unsaved(d);
+        }
+        displays
+    }
+
+    /// Writes back displays from `marked_displays`, dropping the marks.
+    pub fn put_marked_displays(&mut self, displays: HashMap<String, Display>) {
+        for (name, mut d) in displays.into_iter() {
+            d.unapplied = false;
+            d.unsaved = false;
+            // Only replace what was changed, so unchanged displays stay
+            // shared with the rest of the model.
+            if self
+                .display_geo
+                .get(&name)
+                .map_or(true, |old| !old.same(&d))
+            {
+                self.display_geo.insert(name, d);
+            }
+        }
+    }
+
+    /// The commands Apply would run and the lines Save would write, for
+    /// the displays with changes.
+    pub fn pending_changes(&self) -> Vector<PendingChange> {
+        let mut displays: Vec<&Display> = self.display_geo.values().collect();
+        displays.sort_by(|a, b| a.name.cmp(&b.name));
+
+        displays
+            .into_iter()
+            .filter_map(|d| {
+                let command = self
+                    .live
+                    .get(&d.name)
+                    .and_then(|live| d.apply_command(live))
+                    .unwrap_or_default();
+                let config = if self.is_unsaved(d) {
+                    self.config_lines(d).join("\n")
+                } else {
+                    String::new()
+                };
+                if command.is_empty() && config.is_empty() {
+                    return None;
+                }
+                Some(PendingChange {
+                    name: d.name.clone(),
+                    command,
+                    config,
+                })
+            })
+            .collect()
+    }
+
+    /// Puts a display back to its live settings, dropping the edits which
+    /// were not applied. The live settings include those sway doesn't
+    /// report, as saved or last applied.
+    pub fn reset_display(&mut self, name: &str) {
+        let live = match self.live.get(name) {
+            Some(live) => live.clone(),
+            None => return,
+        };
+        if let Some(d) = self.display_geo.get_mut(name) {
+            let mut reset = live;
+            reset.focused = d.focused;
+            reset.use_identifier = d.use_identifier;
+            *d = reset;
+        }
+    }
+
+    /// Resets the displays to their live state, keeping the settings which
+    /// sway does not report.
+    pub fn adopt_live(&mut self) {
+        let mut live = self.live.clone();
+        for (name, d) in live.iter_mut() {
+            if let Some(ours) = self.display_geo.get(name) {
+                d.keep_unreported(ours);
+                d.focused = ours.focused;
+            }
+        }
+        self.display_geo = live;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+
+    #[test]
+    fn split_args_honours_quotes() {
+        assert_eq!(
+            split_args(r#"output "Dell Inc. U2720Q 1234"  scale 2"#),
+            vec!["output", "Dell Inc. U2720Q 1234", "scale", "2"]
+        );
+        assert_eq!(split_args(r#"bg "" fill"#), vec!["bg", "", "fill"]);
+        assert_eq!(quote_arg("DP-1"), "DP-1");
+        assert_eq!(quote_arg("Dell Inc. U2720Q"), r#""Dell Inc. U2720Q""#);
+        assert_eq!(quote_arg(""), r#""""#);
+    }
+
+    #[test]
+    fn parses_output_directive() {
+        let d: Display = r#"output "Dell Inc. U2720Q 1234" scale 1.5 pos 10 -20 transform 90 resolution 3840x2160@60Hz adaptive_sync on"#
+            .parse()
+            .unwrap();
+        assert_eq!(d.name, "Dell Inc. U2720Q 1234");
+        assert!(d.active);
+        assert_eq!(d.scale, Scale(1.5));
+        assert_eq!(d.position, Pos(10, -20));
+        assert_eq!(d.transform, Transform::R90);
+        assert!(d.adaptive_sync);
+        let m = d.modes.iter().find(|m| m.selected).unwrap();
+        assert_eq!((m.width, m.height, m.refresh), (3840, 2160, 60_000));
+    }
+
+    #[test]
+    fn keeps_unknown_options() {
+        let d: Display =
+            "output DP-1 scale 2 scale_filter nearest dpms on transform 90 clockwise disable"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            d.extra.iter().cloned().collect::<Vec<_>>(),
+            vec!["scale_filter nearest", "dpms on", "transform 90 clockwise"]
+        );
+        assert!(!d.active);
+
+        let config = d.config();
+        assert!(config.contains(" scale_filter nearest dpms on transform 90 clockwise"));
+        let again: Display = config.parse().unwrap();
+        assert!(again.extra.same(&d.extra));
+    }
+
+    #[test]
+    fn config_round_trips() {
+        for line in [
+            "output DP-1 scale 1.00",
+            r#"output "Goldstar Company Ltd LG HDR 4K 0x0000B4F1" scale 1.50 pos 3840 0 transform flipped-270 resolution 3840x2160@59.99Hz"#,
+            "output eDP-1 scale 2.00 mode --custom 2560x1440@75.00Hz subpixel rgb render_bit_depth 10 max_render_time 4 allow_tearing yes",
+            r#"output HDMI-A-1 scale 1.00 bg "/home/me/Pictures/a b.png" fit disable"#,
+            "output DP-2 scale 1.00 modeline 173 1920 2048 2248 2576 1080 1083 1088 1120 -hsync +vsync",
+        ] {
+            let d: Display = line.parse().unwrap();
+            assert_eq!(d.config(), line);
+        }
+    }
+
+    #[test]
+    fn transforms_round_trip() {
+        for t in [
+            Transform::None,
+            Transform::R90,
+            Transform::R180,
+            Transform::R270,
+            Transform::Flipped,
+            Transform::Flipped90,
+            Transform::Flipped180,
+            Transform::Flipped270,
+        ] {
+            assert_eq!(t.to_string().parse::<Transform>().unwrap(), t);
+        }
+        assert_eq!("0".parse::<Transform>().unwrap(), Transform::None);
+    }
+
+    #[test]
+    fn parse_config_skips_comments_and_other_lines() {
+        let config = "# output DP-9 scale 3\n\
+                      \n\
+                      exec_always foo\n\
+                      output * bg /tmp/wall.png fill\n\
+                      output DP-1 scale 2\n\
+                      \toutput HDMI-A-1 disable\n";
+        let displays = parse_config(config).unwrap();
+        let names: Vec<&str> = displays.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["DP-1", "HDMI-A-1"]);
+        assert!(!displays[1].active);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for line in [
+            "output",
+            "workspace 1 output DP-1",
+            "output DP-1 scale",
+            "output DP-1 scale abc",
+            "output DP-1 pos 10",
+            "output DP-1 pos a b",
+            "output DP-1 transform 45",
+            "output DP-1 resolution 1920",
+            "output DP-1 adaptive_sync maybe",
+            "output DP-1 bg /a.png sideways",
+            "output DP-1 modeline 173 1920 2048",
+        ] {
+            assert!(
+                line.parse::<Display>().is_err(),
+                "{:?} should not parse",
+                line
+            );
+        }
+        assert!(parse_config("output DP-1 scale 2\noutput DP-2 pos x y\n").is_err());
+    }
+
+    #[test]
+    fn loads_scales_out_of_range() {
+        let displays = parse_config("output DP-1 scale 12\noutput DP-2 scale 0\n").unwrap();
+        assert_eq!(displays[0].scale, Scale(12.));
+        assert!(displays[0].scale.validate().is_err());
+        assert!(displays[1].scale.validate().is_err());
+        assert!(Scale(MAX_SCALE).validate().is_ok());
+    }
+
+    #[test]
+    fn scale_commands_keep_the_step() {
+        assert_eq!(Scale(1.).command(), "1.00");
+        assert_eq!(Scale(1.5).command(), "1.50");
+        assert_eq!(Scale(1.25).command(), "1.25");
+        assert_eq!(Scale(121. / 120.).command(), "1.0083");
+        assert_eq!(Scale(4. / 3.).command(), "1.3333");
+    }
+
+    #[test]
+    fn suggests_scales_with_whole_sizes() {
+        let mut d: Display = "output DP-1 resolution 1920x1080".parse().unwrap();
+        let suggestions = d.scale_suggestions();
+        let scales: Vec<String> = suggestions.iter().map(|s| s.scale.to_string()).collect();
+        assert_eq!(
+            scales,
+            vec![
+                "1", "1.2", "1.25", "1.3333", "1.5", "1.6", "1.6667", "1.875", "2", "2.4", "2.5",
+                "2.6667", "3"
+            ]
+        );
+        assert_eq!(suggestions[2].size, (1536, 864));
+        assert_eq!(suggestions[8].size, (960, 540));
+
+        d.transform = Transform::R90;
+        assert_eq!(d.scale_suggestions()[4].size, (720, 1280));
+
+        d.modes = Vector::new();
+        assert!(d.scale_suggestions().is_empty());
+    }
+
+    fn fake_app() -> (App, FakeBackend) {
+        let mut backend = FakeBackend::new();
+        let app = App::from(backend.outputs().unwrap());
+        (app, backend)
+    }
+
+    fn move_display(app: &mut App, name: &str, x: i32) {
+        app.display_geo.get_mut(name).unwrap().position = (x, 0).into();
+    }
+
+    fn backend_position(backend: &mut FakeBackend, name: &str) -> Pos {
+        let outputs = backend.outputs().unwrap();
+        outputs.iter().find(|d| d.name == name).unwrap().position
+    }
+
+    #[test]
+    fn apply_displays_updates_outputs_and_live() {
+        let (mut app, mut backend) = fake_app();
+        move_display(&mut app, "DP-1", 5000);
+
+        app.apply_displays(&mut backend).unwrap();
+        assert_eq!(backend_position(&mut backend, "DP-1"), Pos(5000, 0));
+        assert_eq!(app.live["DP-1"].position, Pos(5000, 0));
+        assert!(app.unapplied_settings(&app.display_geo["DP-1"]).is_empty());
+    }
+
+    #[test]
+    fn apply_displays_reports_backend_errors() {
+        let (mut app, mut backend) = fake_app();
+        app.display_geo.get_mut("DP-1").unwrap().scale = Scale(0.);
+        assert!(app.apply_displays(&mut backend).is_err());
+    }
+
+    #[test]
+    fn applying_without_changes_needs_no_confirmation() {
+        let (mut app, mut backend) = fake_app();
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        assert!(app.pending_revert.is_none());
+    }
+
+    #[test]
+    fn rotation_is_keyed_like_the_saved_config() {
+        let (mut app, _) = fake_app();
+        app.use_identifiers = true;
+        let d = app.display_geo.get_mut("DP-1").unwrap();
+        d.background = Background {
+            source: "/home/me/Pictures/a.png".into(),
+            mode: BackgroundMode::Fill,
+            rotate_minutes: 5,
+        };
+        let d = app.display_geo["DP-1"].clone();
+
+        let lines = app.config_lines(&d);
+        let rotation = RotateOpts::from_config(&lines[1]).unwrap();
+        assert_eq!(rotation.output, d.identifier());
+        assert_eq!(app.as_saved(&d).config_name(), rotation.output);
+    }
+
+    #[test]
+    fn revert_restores_the_outputs() {
+        let (mut app, mut backend) = fake_app();
+        let original = app.live["DP-1"].position;
+
+        move_display(&mut app, "DP-1", 5000);
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        assert!(app.pending_revert.is_some());
+
+        app.revert_displays(&mut backend).unwrap();
+        assert_eq!(backend_position(&mut backend, "DP-1"), original);
+        assert_eq!(app.display_geo["DP-1"].position, original);
+        assert!(app.pending_revert.is_none());
+
+        // Nothing left to revert.
+        app.revert_displays(&mut backend).unwrap();
+        assert_eq!(backend_position(&mut backend, "DP-1"), original);
+    }
+
+    #[test]
+    fn exiting_reverts_unconfirmed_settings() {
+        let (mut app, mut backend) = fake_app();
+        let original = app.live["DP-1"].position;
+
+        move_display(&mut app, "DP-1", 5000);
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        app.on_exit(&mut backend).unwrap();
+        assert_eq!(backend_position(&mut backend, "DP-1"), original);
+
+        // Confirmed settings stay.
+        move_display(&mut app, "DP-1", 5000);
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        app.keep_displays();
+        app.on_exit(&mut backend).unwrap();
+        assert_eq!(backend_position(&mut backend, "DP-1"), Pos(5000, 0));
+    }
+
+    #[test]
+    fn merge_outputs_keeps_edits_across_hotplug() {
+        let (mut app, mut backend) = fake_app();
+        move_display(&mut app, "DP-1", 5000);
+        app.display_geo.get_mut("eDP-1").unwrap().focused = true;
+
+        let hdmi = backend.unplug("HDMI-A-1").unwrap();
+        assert!(app.merge_outputs(backend.outputs().unwrap()));
+        assert!(!app.display_geo.contains_key("HDMI-A-1"));
+        assert_eq!(app.display_geo["DP-1"].position, Pos(5000, 0));
+        assert!(app.display_geo["eDP-1"].focused);
+
+        // The same outputs again are not a change.
+        assert!(!app.merge_outputs(backend.outputs().unwrap()));
+
+        backend.plug(hdmi);
+        assert!(app.merge_outputs(backend.outputs().unwrap()));
+        assert!(app.display_geo.contains_key("HDMI-A-1"));
+        assert_eq!(app.display_geo["DP-1"].position, Pos(5000, 0));
+    }
+
+    /// Loads a displays file with the given contents into the app.
+    fn load_config_str(app: &mut App, name: &str, contents: &str) {
+        let dir = std::env::temp_dir().join(format!("twl-display-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("displays"), contents).unwrap();
+        app.load_config(dir.clone()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saved_unreported_settings_are_not_pending() {
+        let (mut app, _backend) = fake_app();
+        load_config_str(
+            &mut app,
+            "unreported",
+            "output DP-1 enable position 1536 0 scale 1 bg /tmp/wall.png fill dpms on\n",
+        );
+
+        let d = &app.display_geo["DP-1"];
+        assert_eq!(d.background.source, "/tmp/wall.png");
+        assert!(app.unapplied_settings(d).is_empty());
+        assert!(app.pending_changes().iter().all(|p| p.command.is_empty()));
+    }
+
+    #[test]
+    fn save_keeps_output_lines_it_does_not_manage() {
+        let (mut app, _backend) = fake_app();
+        let dir = std::env::temp_dir().join(format!("twl-display-keep-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let contents = "output * bg /tmp/wall.png fill\n\
+                        output DP-1 pos 10 0\n\
+                        output DP-9 pos x y\n";
+        std::fs::write(dir.join("displays"), contents).unwrap();
+
+        // The unreadable line is reported, but doesn't stop the rest loading.
+        assert!(app.load_config(dir.clone()).is_err());
+        assert_eq!(app.display_geo["DP-1"].position, Pos(10, 0));
+
+        app.save_config(dir.clone()).unwrap();
+        let saved = std::fs::read_to_string(dir.join("displays")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let lines: Vec<&str> = saved.lines().collect();
+        assert!(lines.contains(&"output * bg /tmp/wall.png fill"));
+        assert!(lines.contains(&"output DP-9 pos x y"));
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with("output DP-1 "))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn scale_is_only_written_once_chosen() {
+        let (mut app, mut backend) = fake_app();
+        let has_scale =
+            |app: &App| app.config_lines(&app.display_geo["DP-1"])[0].contains(" scale ");
+        assert!(!has_scale(&app));
+
+        app.display_geo.get_mut("DP-1").unwrap().scale = Scale(2.);
+        assert!(has_scale(&app));
+
+        // Still chosen once sway reports it back.
+        app.apply_displays(&mut backend).unwrap();
+        assert!(has_scale(&app));
+
+        // A saved line without a scale keeps the one sway picked.
+        let d: Display = "output eDP-1 pos 10 0".parse().unwrap();
+        assert!(!d.config().contains(" scale "));
+        let mut live = app.live["eDP-1"].clone();
+        live.apply_saved(&d);
+        assert_eq!(live.scale, Scale(1.25));
+    }
+
+    #[test]
+    fn reset_display_keeps_saved_unreported_settings() {
+        let (mut app, _backend) = fake_app();
+        load_config_str(
+            &mut app,
+            "reset",
+            "output DP-1 enable position 1536 0 scale 1 bg /tmp/wall.png fill dpms on\n",
+        );
+        let saved = app.display_geo["DP-1"].clone();
+        move_display(&mut app, "DP-1", 5000);
+
+        app.reset_display("DP-1");
+        let d = &app.display_geo["DP-1"];
+        assert_eq!(d.position, saved.position);
+        assert_eq!(d.background, saved.background);
+        assert!(d.extra.same(&saved.extra));
+        assert!(app.unapplied_settings(d).is_empty());
+    }
+
+    #[test]
+    fn reapplying_keeps_the_first_revert_point() {
+        let (mut app, mut backend) = fake_app();
+        let original = app.live["DP-1"].position;
+
+        move_display(&mut app, "DP-1", 5000);
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        app.pending_revert.as_mut().unwrap().seconds_left = 3;
+
+        move_display(&mut app, "DP-1", 6000);
+        app.apply_displays_with_confirm(&mut backend).unwrap();
+        let revert = app.pending_revert.as_ref().unwrap();
+        assert_eq!(revert.seconds_left, REVERT_TIMEOUT_SECS);
+        assert_eq!(revert.previous["DP-1"].position, original);
+
+        app.revert_displays(&mut backend).unwrap();
+        assert_eq!(app.live["DP-1"].position, original);
+        assert!(app.pending_revert.is_none());
+    }
+
+    #[test]
+    fn keep_unreported_keeps_unknown_options() {
+        let saved: Display = "output DP-1 scale 2 dpms on adaptive_sync on"
+            .parse()
+            .unwrap();
+        let mut live: Display = "output DP-1 scale 2".parse().unwrap();
+        live.keep_unreported(&saved);
+        assert!(live.adaptive_sync);
+        assert!(live.extra.same(&saved.extra));
+    }
+
+    #[test]
+    fn adopt_live_keeps_unknown_options() {
+        let (mut app, _backend) = fake_app();
+        let saved: Display = "output DP-1 dpms on".parse().unwrap();
+        app.display_geo.get_mut("DP-1").unwrap().extra = saved.extra.clone();
+        move_display(&mut app, "DP-1", 5000);
+
+        app.adopt_live();
+        let d = &app.display_geo["DP-1"];
+        assert_eq!(d.position, app.live["DP-1"].position);
+        assert!(d.extra.same(&saved.extra));
     }
 }
@@ -0,0 +1,140 @@
+use druid::im::{HashMap, Vector};
+use druid::Data;
+
+use crate::model::display::Display;
+
+pub mod commands {
+    use druid::Selector;
+
+    // UI commands
+    pub const UNDO: Selector<()> = Selector::new("undo");
+    pub const REDO: Selector<()> = Selector::new("redo");
+}
+
+/// How many steps can be undone; older ones are dropped.
+const MAX_STEPS: usize = 100;
+
+type Layout = HashMap<String, Display>;
+
+/// Undo and redo stacks of display layouts. Snapshots are cheap, as the
+/// layout is an `im` map sharing its unchanged displays.
+#[derive(Clone, Default, Data)]
+pub struct History {
+    undo: Vector<Layout>,
+    redo: Vector<Layout>,
+}
+
+impl History {
+    /// Records the layout from before an edit.
+    pub fn record(&mut self, before: Layout) {
+        self.undo.push_back(before);
+        while self.undo.len() > MAX_STEPS {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// Steps back, returning the layout to restore.
+    pub fn undo(&mut self, current: &Layout) -> Option<Layout> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push_back(current.clone());
+        Some(previous)
+    }
+
+    /// Steps forward again, returning the layout to restore.
+    pub fn redo(&mut self, current: &Layout) -> Option<Layout> {
+        let next = self.redo.pop_back()?;
+        self.undo.push_back(current.clone());
+        Some(next)
+    }
+}
+
+/// Whether two layouts differ in anything other than which display has
+/// focus, which isn't worth an undo step.
+pub fn edited(before: &Layout, after: &Layout) -> bool {
+    before.len() != after.len()
+        || before.iter().any(|(name, b)| match after.get(name) {
+            Some(a) => {
+                let mut a = a.clone();
+                a.focused = b.focused;
+                !a.same(b)
+            }
+            None => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(x: i32) -> Layout {
+        let d = Display {
+            name: "DP-1".to_string(),
+            position: (x, 0).into(),
+            ..Display::default()
+        };
+        HashMap::unit(d.name.clone(), d)
+    }
+
+    fn x(layout: &Layout) -> i32 {
+        layout["DP-1"].position.0
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_the_layouts() {
+        let mut history = History::default();
+        history.record(layout(0));
+        history.record(layout(1));
+
+        let current = layout(2);
+        let previous = history.undo(&current).unwrap();
+        assert_eq!(x(&previous), 1);
+        let previous = history.undo(&previous).unwrap();
+        assert_eq!(x(&previous), 0);
+        assert!(history.undo(&previous).is_none());
+
+        let next = history.redo(&previous).unwrap();
+        assert_eq!(x(&next), 1);
+        let next = history.redo(&next).unwrap();
+        assert_eq!(x(&next), 2);
+        assert!(history.redo(&next).is_none());
+    }
+
+    #[test]
+    fn recording_drops_the_redo_steps() {
+        let mut history = History::default();
+        history.record(layout(0));
+        history.undo(&layout(1)).unwrap();
+
+        history.record(layout(0));
+        assert!(history.redo(&layout(5)).is_none());
+    }
+
+    #[test]
+    fn keeps_at_most_max_steps() {
+        let mut history = History::default();
+        for i in 0..MAX_STEPS as i32 + 10 {
+            history.record(layout(i));
+        }
+
+        let mut current = layout(-1);
+        let mut steps = 0;
+        while let Some(previous) = history.undo(&current) {
+            current = previous;
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_STEPS);
+        assert_eq!(x(&current), 10);
+    }
+
+    #[test]
+    fn focus_alone_is_not_an_edit() {
+        let before = layout(0);
+        let mut after = before.clone();
+        after.get_mut("DP-1").unwrap().focused = true;
+        assert!(!edited(&before, &after));
+
+        assert!(edited(&before, &layout(1)));
+        assert!(edited(&before, &HashMap::new()));
+    }
+}
@@ -1,2 +1,9 @@
+pub mod backlight;
 pub mod bluetooth;
+pub mod config;
 pub mod display;
+pub mod history;
+pub mod nightlight;
+pub mod notification;
+pub mod profile;
+pub mod workspace;
@@ -0,0 +1,441 @@
+//! Night light settings, carried out by wlsunset. The schedule is started
+//! from a managed `exec` line, and gammastep previews a temperature.
+
+use druid::{Data, Lens};
+
+use std::path::Path;
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+
+use crate::model::config;
+use crate::model::display::{quote_arg, split_args, ConfigParseError};
+use crate::model::workspace::OutputList;
+
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum Schedule {
+    /// Fixed sunset and sunrise times.
+    Fixed,
+    /// Sunset and sunrise worked out from a latitude and longitude.
+    Location,
+}
+
+/// A time of day, as HH:MM.
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub struct TimeOfDay(pub u8, pub u8);
+
+impl std::str::FromStr for TimeOfDay {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ConfigParseError(format!("invalid time {:?}, expected HH:MM", s));
+        let (h, m) = s.trim().split_once(':').ok_or_else(err)?;
+        let (h, m): (u8, u8) = (h.parse().map_err(|_| err())?, m.parse().map_err(|_| err())?);
+        if h > 23 || m > 59 {
+            return Err(err());
+        }
+        Ok(TimeOfDay(h, m))
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.0, self.1)
+    }
+}
+
+#[derive(Clone, Data, Debug, Lens)]
+pub struct NightLight {
+    pub enabled: bool,
+    /// Colour temperatures in Kelvin, at night and during the day.
+    pub low_temp: u32,
+    pub high_temp: u32,
+
+    pub schedule: Schedule,
+    pub sunset: TimeOfDay,
+    pub sunrise: TimeOfDay,
+    pub latitude: f64,
+    pub longitude: f64,
+
+    /// The outputs to change the temperature of, or all when empty.
+    pub outputs: OutputList,
+
+    /// Whether the night temperature is being previewed right now.
+    pub preview: bool,
+}
+
+impl Default for NightLight {
+    fn default() -> Self {
+        NightLight {
+            enabled: false,
+            low_temp: 4000,
+            high_temp: 6500,
+            schedule: Schedule::Fixed,
+            sunset: TimeOfDay(19, 30),
+            sunrise: TimeOfDay(7, 0),
+            latitude: 0.,
+            longitude: 0.,
+            outputs: OutputList::default(),
+            preview: false,
+        }
+    }
+}
+
+const CONFIG_HEADER: &str = "# Night light settings written by twl-configurator. The wlsunset\n\
+                             # line is rewritten on save, comments and other lines are kept.";
+
+/// The key of the managed line in the night light file.
+const MANAGED_KEY: &str = "wlsunset";
+
+impl NightLight {
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-t".to_string(),
+            self.low_temp.to_string(),
+            "-T".to_string(),
+            self.high_temp.to_string(),
+        ];
+        match self.schedule {
+            Schedule::Fixed => args.extend([
+                "-S".to_string(),
+                self.sunrise.to_string(),
+                "-s".to_string(),
+                self.sunset.to_string(),
+            ]),
+            Schedule::Location => args.extend([
+                "-l".to_string(),
+                self.latitude.to_string(),
+                "-L".to_string(),
+                self.longitude.to_string(),
+            ]),
+        }
+        for o in self.outputs.0.iter() {
+            args.push("-o".to_string());
+            args.push(o.clone());
+        }
+        args
+    }
+
+    /// The sway config line which starts wlsunset.
+    pub fn config(&self) -> String {
+        let mut line = "exec wlsunset".to_string();
+        for a in self.args() {
+            line.push(' ');
+            line.push_str(&quote_arg(&a));
+        }
+        line
+    }
+
+    /// Parses a config line written by `config`. Options we don't know are
+    /// ignored.
+    pub fn from_config(line: &str) -> Option<NightLight> {
+        let args = split_args(line.trim().strip_prefix("exec ")?);
+        if args.first()? != "wlsunset" {
+            return None;
+        }
+
+        let mut nl = NightLight {
+            enabled: true,
+            ..NightLight::default()
+        };
+        let mut outputs = Vec::new();
+        for pair in args[1..].chunks(2) {
+            let (flag, value) = match pair {
+                [flag, value] => (flag.as_str(), value),
+                _ => break,
+            };
+            match flag {
+                "-t" => nl.low_temp = value.parse().unwrap_or(nl.low_temp),
+                "-T" => nl.high_temp = value.parse().unwrap_or(nl.high_temp),
+                "-S" => nl.sunrise = value.parse().unwrap_or(nl.sunrise),
+                "-s" => nl.sunset = value.parse().unwrap_or(nl.sunset),
+                "-l" => {
+                    nl.schedule = Schedule::Location;
+                    nl.latitude = value.parse().unwrap_or(nl.latitude);
+                }
+                "-L" => {
+                    nl.schedule = Schedule::Location;
+                    nl.longitude = value.parse().unwrap_or(nl.longitude);
+                }
+                "-o" => outputs.push(value.clone()),
+                _ => {}
+            }
+        }
+        nl.outputs = OutputList(outputs.into_iter().collect());
+        Some(nl)
+    }
+
+    /// Checks the settings make sense to wlsunset.
+    pub fn validate(&self) -> Result<(), ConfigParseError> {
+        if self.low_temp < 1000 || self.high_temp > 25000 {
+            return Err(ConfigParseError(
+                "temperatures must be between 1000K and 25000K".into(),
+            ));
+        }
+        if self.low_temp >= self.high_temp {
+            return Err(ConfigParseError(
+                "the night temperature must be lower than the day temperature".into(),
+            ));
+        }
+        if self.schedule == Schedule::Location
+            && (self.latitude.abs() > 90. || self.longitude.abs() > 180.)
+        {
+            return Err(ConfigParseError(format!(
+                "invalid location {}, {}",
+                self.latitude, self.longitude
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the settings from the night light file.
+    pub fn load(base_path: &Path) -> Result<NightLight, std::io::Error> {
+        let contents = config::read(&base_path.join("nightlight"))?;
+        Ok(contents
+            .lines()
+            .find_map(NightLight::from_config)
+            .unwrap_or_default())
+    }
+
+    /// Writes the settings to the night light file. They take effect when
+    /// wlsunset is restarted, see `Job::Restart`.
+    pub fn save(&self, base_path: &Path) -> Result<(), std::io::Error> {
+        self.validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let path = base_path.join("nightlight");
+        let blocks = if self.enabled {
+            vec![(MANAGED_KEY.to_string(), vec![self.config()])]
+        } else {
+            vec![]
+        };
+        let contents = config::merge(&config::read(&path)?, CONFIG_HEADER, &blocks, |line| {
+            NightLight::from_config(line).map(|_| MANAGED_KEY.to_string())
+        });
+        config::write_atomic(&path, &contents)
+    }
+
+    /// Stops wlsunset, and starts it again with these settings if enabled.
+    fn restart(&self) -> Result<(), std::io::Error> {
+        stop("wlsunset")?;
+        if self.enabled {
+            spawn(Command::new("wlsunset").args(self.args()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Work on the wlsunset and gammastep processes.
+pub enum Job {
+    /// Restarts wlsunset with the saved settings. While previewing, they
+    /// are started once the preview ends instead.
+    Restart(NightLight),
+    /// Starts or stops previewing the night temperature.
+    Preview(NightLight),
+}
+
+/// What was changed to show a preview, so that it can be undone.
+#[derive(Default)]
+struct Preview {
+    /// The gammastep showing the night temperature.
+    gammastep: Option<Child>,
+    /// Command lines of the wlsunset processes stopped for the preview, to
+    /// start again once it ends. `None` when not previewing.
+    stopped: Option<Vec<Vec<String>>>,
+}
+
+impl Preview {
+    fn show(&mut self, temp: u32) -> Result<(), std::io::Error> {
+        if self.stopped.is_none() {
+            self.stopped = Some(running(Path::new("/proc"), "wlsunset"));
+            stop("wlsunset")?;
+        }
+        self.stop_gammastep();
+        let child = Command::new("gammastep")
+            .args(["-P", "-O", &temp.to_string()])
+            .spawn()?;
+        self.gammastep = Some(child);
+        Ok(())
+    }
+
+    /// Goes back to whatever was running before the preview.
+    fn end(&mut self) -> Result<(), std::io::Error> {
+        self.stop_gammastep();
+        for cmd in self.stopped.take().unwrap_or_default() {
+            spawn(Command::new(&cmd[0]).args(&cmd[1..]))?;
+        }
+        Ok(())
+    }
+
+    fn restart(&mut self, nl: &NightLight) -> Result<(), std::io::Error> {
+        match &mut self.stopped {
+            Some(stopped) => {
+                *stopped = if nl.enabled {
+                    let mut cmd = vec!["wlsunset".to_string()];
+                    cmd.extend(nl.args());
+                    vec![cmd]
+                } else {
+                    Vec::new()
+                };
+                Ok(())
+            }
+            None => nl.restart(),
+        }
+    }
+
+    fn stop_gammastep(&mut self) {
+        if let Some(mut child) = self.gammastep.take() {
+            // It may have exited by itself already.
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Runs jobs one after the other on a background thread, so the UI never
+/// waits on the processes. Dropping it ends any preview.
+pub struct Worker {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Starts the thread. Failures are passed to `on_error` along with what
+    /// was being done.
+    pub fn new<F>(on_error: F) -> Self
+    where
+        F: Fn(&str, std::io::Error) + Send + 'static,
+    {
+        let (jobs, rx) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            let mut preview = Preview::default();
+            for job in rx {
+                let result = match &job {
+                    Job::Restart(nl) => preview.restart(nl),
+                    Job::Preview(nl) if nl.preview => preview.show(nl.low_temp),
+                    Job::Preview(_) => preview.end(),
+                };
+                if let Err(e) = result {
+                    match job {
+                        Job::Restart(_) => on_error("Failed to restart wlsunset", e),
+                        Job::Preview(_) => on_error("Failed to preview the night light", e),
+                    }
+                }
+            }
+
+            if let Err(e) = preview.end() {
+                on_error("Failed to end the night light preview", e);
+            }
+        });
+        Worker {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn run(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            // The thread only stops once we are dropped.
+            let _ = jobs.send(job);
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread, which waits for it to put
+        // back what the preview replaced before the app exits.
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Starts a process, and waits for it in the background so that it doesn't
+/// linger as a zombie once it exits.
+fn spawn(command: &mut Command) -> Result<(), std::io::Error> {
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Stops all processes with the given name.
+fn stop(name: &str) -> Result<(), std::io::Error> {
+    // pkill fails when nothing matched, which is fine.
+    Command::new("pkill").args(["-x", name]).status()?;
+    Ok(())
+}
+
+/// The command lines of the processes with the given name, found in the
+/// proc filesystem at `proc_root`.
+fn running(proc_root: &Path, name: &str) -> Vec<Vec<String>> {
+    let entries = match std::fs::read_dir(proc_root) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut commands = Vec::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        // Processes can exit while we look, so skip whatever can't be read.
+        let comm = std::fs::read_to_string(dir.join("comm")).unwrap_or_default();
+        if comm.trim_end() != name {
+            continue;
+        }
+        let cmdline = std::fs::read(dir.join("cmdline")).unwrap_or_default();
+        let cmd: Vec<String> = cmdline
+            .split(|b| *b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+        if !cmd.is_empty() {
+            commands.push(cmd);
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_running_command_lines() {
+        let root = std::env::temp_dir().join(format!("twl-proc-{}", std::process::id()));
+        for (pid, comm, cmdline) in [
+            ("100", "wlsunset\n", &b"wlsunset\0-t\x003500\0"[..]),
+            ("200", "sway\n", &b"sway\0"[..]),
+        ] {
+            std::fs::create_dir_all(root.join(pid)).unwrap();
+            std::fs::write(root.join(pid).join("comm"), comm).unwrap();
+            std::fs::write(root.join(pid).join("cmdline"), cmdline).unwrap();
+        }
+        // Gone before its files could be read.
+        std::fs::create_dir_all(root.join("300")).unwrap();
+
+        let found = running(&root, "wlsunset");
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(found, vec![vec!["wlsunset", "-t", "3500"]]);
+    }
+
+    #[test]
+    fn saving_while_previewing_changes_what_is_put_back() {
+        let mut preview = Preview {
+            gammastep: None,
+            stopped: Some(vec![vec!["wlsunset".to_string()]]),
+        };
+        let mut nl = NightLight {
+            enabled: true,
+            low_temp: 3500,
+            ..NightLight::default()
+        };
+        preview.restart(&nl).unwrap();
+        let stopped = preview.stopped.as_ref().unwrap();
+        assert_eq!(stopped[0][..3], ["wlsunset", "-t", "3500"]);
+
+        nl.enabled = false;
+        preview.restart(&nl).unwrap();
+        assert_eq!(preview.stopped, Some(Vec::new()));
+    }
+}
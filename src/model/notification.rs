@@ -0,0 +1,65 @@
+use druid::im::Vector;
+use druid::{Data, Lens};
+
+pub mod commands {
+    use druid::Selector;
+
+    // worker -> UI commands
+    pub const NOTIFY_ERROR: Selector<String> = Selector::new("notify_error");
+
+    // UI commands
+    pub const DISMISS_NOTIFICATION: Selector<u64> = Selector::new("dismiss_notification");
+}
+
+/// How many notifications are shown at once; older ones are dropped.
+const MAX_NOTIFICATIONS: usize = 4;
+
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Data, Debug, Lens)]
+pub struct Notification {
+    pub id: u64,
+    pub level: Level,
+    pub message: String,
+}
+
+/// Messages shown to the user in the window until they are dismissed.
+#[derive(Clone, Default, Data, Lens)]
+pub struct Notifications {
+    pub items: Vector<Notification>,
+    next_id: u64,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, message: String) {
+        // Repeating the same message only makes the list longer.
+        self.items.retain(|n| n.message != message);
+        self.items.push_back(Notification {
+            id: self.next_id,
+            level,
+            message,
+        });
+        self.next_id += 1;
+
+        while self.items.len() > MAX_NOTIFICATIONS {
+            self.items.pop_front();
+        }
+    }
+
+    /// Reports that something failed, and why.
+    pub fn error(&mut self, what: &str, err: impl std::fmt::Display) {
+        self.push(Level::Error, format!("{}: {}", what, err));
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Level::Info, message.into());
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.items.retain(|n| n.id != id);
+    }
+}
@@ -0,0 +1,170 @@
+use druid::im::{HashMap, Vector};
+use druid::{Data, Lens};
+
+use std::path::PathBuf;
+
+use crate::model::config;
+use crate::model::display::{parse_config, Display};
+
+/// A named set of display settings, keyed by the make/model/serial
+/// identifier of each display rather than the connector it is plugged into.
+#[derive(Clone, Default, Data, Lens)]
+pub struct Profile {
+    pub name: String,
+    pub displays: HashMap<String, Display>,
+}
+
+/// The key of a display in a profile. Displays without a serial are keyed
+/// by connector instead, so that two identical monitors stay apart.
+fn key(d: &Display) -> String {
+    if d.serial.is_empty() {
+        d.name.clone()
+    } else {
+        d.identifier()
+    }
+}
+
+impl Profile {
+    pub fn from_displays(name: String, displays: &HashMap<String, Display>) -> Self {
+        let mut out = HashMap::new();
+        for (_, d) in displays.iter() {
+            let mut d = d.clone();
+            d.name = key(&d);
+            d.use_identifier = false;
+            // The profile keeps the scale in use, even if sway picked it.
+            d.scale_set = true;
+            d.focused = false;
+            out.insert(d.name.clone(), d);
+        }
+
+        Profile {
+            name,
+            displays: out,
+        }
+    }
+
+    /// Whether the profile describes exactly the given set of displays.
+    pub fn matches(&self, displays: &HashMap<String, Display>) -> bool {
+        self.displays.len() == displays.len()
+            && displays
+                .iter()
+                .all(|(_, d)| self.displays.contains_key(&key(d)))
+    }
+
+    /// Overlays the settings in the profile onto the displays which
+    /// share a key with them.
+    pub fn apply(&self, displays: &mut HashMap<String, Display>) {
+        for (_, d) in displays.iter_mut() {
+            if let Some(p) = self.displays.get(&key(d)) {
+                d.apply_saved(p);
+            }
+        }
+    }
+
+    fn dir(mut base_path: PathBuf) -> PathBuf {
+        base_path.push("profiles");
+        base_path
+    }
+
+    /// Reads all profiles saved under the config base directory.
+    pub fn load_all(base_path: PathBuf) -> Result<Vector<Profile>, std::io::Error> {
+        let dir = match std::fs::read_dir(Profile::dir(base_path)) {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vector::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut profiles = Vector::new();
+        for entry in dir {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(n) if !n.starts_with('.') && !n.ends_with(".bak") && !n.ends_with(".tmp") => {
+                    n.to_string()
+                }
+                _ => continue,
+            };
+
+            let displays = parse_config(&std::fs::read_to_string(&path)?)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            profiles.push_back(Profile {
+                name,
+                displays: displays.into_iter().map(|d| (d.name.clone(), d)).collect(),
+            });
+        }
+        profiles.sort_by(|a: &Profile, b: &Profile| a.name.cmp(&b.name));
+
+        Ok(profiles)
+    }
+
+    pub fn save(&self, base_path: PathBuf) -> Result<(), std::io::Error> {
+        if self.name.is_empty() || self.name.contains('/') || self.name.starts_with('.') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid profile name {:?}", self.name),
+            ));
+        }
+
+        let mut displays: Vec<&Display> = self.displays.values().collect();
+        displays.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut contents = String::from("# Display profile - automatically generated\n\n");
+        for d in displays {
+            contents.push_str(&d.config());
+            contents.push('\n');
+        }
+
+        let mut path = Profile::dir(base_path);
+        path.push(&self.name);
+        config::write_atomic(&path, &contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(name: &str, serial: &str, x: i32) -> Display {
+        Display {
+            name: name.to_string(),
+            make: "Dell Inc.".to_string(),
+            model: "U2720Q".to_string(),
+            serial: serial.to_string(),
+            active: true,
+            position: (x, 0).into(),
+            ..Display::default()
+        }
+    }
+
+    fn layout(displays: Vec<Display>) -> HashMap<String, Display> {
+        displays.into_iter().map(|d| (d.name.clone(), d)).collect()
+    }
+
+    #[test]
+    fn identical_monitors_without_serial_stay_apart() {
+        let saved = layout(vec![display("DP-1", "", 0), display("DP-2", "", 3840)]);
+        let profile = Profile::from_displays("desk".into(), &saved);
+        assert_eq!(profile.displays.len(), 2);
+
+        let mut connected = layout(vec![display("DP-1", "", 100), display("DP-2", "", 200)]);
+        assert!(profile.matches(&connected));
+        profile.apply(&mut connected);
+        assert_eq!(connected["DP-1"].position, (0, 0).into());
+        assert_eq!(connected["DP-2"].position, (3840, 0).into());
+    }
+
+    #[test]
+    fn monitors_with_serial_follow_the_monitor() {
+        let saved = layout(vec![display("DP-1", "A", 0), display("DP-2", "B", 3840)]);
+        let profile = Profile::from_displays("desk".into(), &saved);
+
+        // Swapped connectors.
+        let mut connected = layout(vec![display("DP-1", "B", 0), display("DP-2", "A", 0)]);
+        assert!(profile.matches(&connected));
+        profile.apply(&mut connected);
+        assert_eq!(connected["DP-1"].position, (3840, 0).into());
+        assert_eq!(connected["DP-2"].position, (0, 0).into());
+
+        let other = layout(vec![display("DP-1", "A", 0), display("DP-2", "C", 0)]);
+        assert!(!profile.matches(&other));
+    }
+}
@@ -0,0 +1,130 @@
+use druid::im::Vector;
+use druid::{Data, Lens};
+
+use std::path::Path;
+
+use crate::model::config;
+use crate::model::display::{quote_arg, split_args, ConfigParseError};
+
+/// The outputs a workspace is placed on, in order of preference. Sway uses
+/// the first of them which is connected. Outputs are connector names or
+/// make/model/serial identifiers, quoted where they contain spaces.
+#[derive(Clone, Debug, Default, Data, PartialEq)]
+pub struct OutputList(pub Vector<String>);
+
+impl std::str::FromStr for OutputList {
+    type Err = ConfigParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.matches('"').count() % 2 != 0 {
+            return Err(ConfigParseError(format!("unbalanced quotes in {:?}", s)));
+        }
+        Ok(OutputList(split_args(s).into_iter().collect()))
+    }
+}
+
+impl std::fmt::Display for OutputList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outputs: Vec<String> = self.0.iter().map(|o| quote_arg(o)).collect();
+        write!(f, "{}", outputs.join(" "))
+    }
+}
+
+#[derive(Clone, Debug, Data, Lens)]
+pub struct Workspace {
+    pub name: String,
+    pub outputs: OutputList,
+}
+
+impl Workspace {
+    /// Parses a `workspace <name> output <outputs...>` rule.
+    pub fn from_config(line: &str) -> Option<Workspace> {
+        let args = split_args(line.trim());
+        if args.len() < 4 || args[0] != "workspace" || args[2] != "output" {
+            return None;
+        }
+
+        Some(Workspace {
+            name: args[1].clone(),
+            outputs: OutputList(args[3..].iter().cloned().collect()),
+        })
+    }
+
+    pub fn config(&self) -> String {
+        format!(
+            "workspace {} output {}",
+            quote_arg(&self.name),
+            self.outputs
+        )
+    }
+
+    /// Sorts numbered workspaces by number, like sway does, and the others
+    /// by name after them.
+    fn sort_key(&self) -> (u64, String) {
+        let digits: String = self
+            .name
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        (digits.parse().unwrap_or(u64::MAX), self.name.clone())
+    }
+}
+
+const CONFIG_HEADER: &str = "# Workspace placement written by twl-configurator. Workspace output\n\
+                             # rules are rewritten on save, comments and other lines are kept.";
+
+/// Reads the workspace rules from the workspaces file.
+pub fn load(base_path: &Path) -> Result<Vec<Workspace>, std::io::Error> {
+    let contents = config::read(&base_path.join("workspaces"))?;
+    Ok(contents
+        .lines()
+        .filter_map(Workspace::from_config)
+        .collect())
+}
+
+/// Adds the named workspaces which don't have a rule yet, and sorts the
+/// list.
+pub fn add_current(workspaces: &mut Vec<Workspace>, current: &[String]) {
+    for name in current {
+        if !workspaces.iter().any(|ws| ws.name == *name) {
+            workspaces.push(Workspace {
+                name: name.clone(),
+                outputs: OutputList::default(),
+            });
+        }
+    }
+    workspaces.sort_by_key(|w| w.sort_key());
+}
+
+/// Writes the rules for the workspaces which have outputs to the workspaces
+/// file, dropping the rules of those which don't.
+pub fn save(base_path: &Path, workspaces: &Vector<Workspace>) -> Result<(), std::io::Error> {
+    let path = base_path.join("workspaces");
+
+    let blocks: Vec<(String, Vec<String>)> = workspaces
+        .iter()
+        .filter(|w| !w.outputs.0.is_empty())
+        .map(|w| (w.name.clone(), vec![w.config()]))
+        .collect();
+
+    let contents = config::merge(&config::read(&path)?, CONFIG_HEADER, &blocks, |line| {
+        Workspace::from_config(line).map(|w| w.name)
+    });
+    config::write_atomic(&path, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_current_adds_missing_workspaces_in_order() {
+        let mut workspaces = vec![Workspace::from_config("workspace 2 output DP-1").unwrap()];
+        let current = ["10", "2", "mail", "1"].map(String::from);
+
+        add_current(&mut workspaces, &current);
+        let names: Vec<&str> = workspaces.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, ["1", "2", "10", "mail"]);
+        assert_eq!(workspaces[1].outputs.to_string(), "DP-1");
+    }
+}
@@ -0,0 +1,160 @@
+//! The wallpaper library, and the background process which cycles the
+//! wallpaper of an output through a directory of images.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use structopt::StructOpt;
+
+use crate::model::display::{quote_arg, split_args, Background, BackgroundMode};
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+/// Options of the `rotate-wallpaper` subcommand.
+#[derive(StructOpt, Debug, PartialEq, Clone)]
+pub struct RotateOpts {
+    /// Minutes to show each image for.
+    #[structopt(long, default_value = "30")]
+    pub interval: u32,
+    /// How images are fitted to the output.
+    #[structopt(long, default_value = "fill")]
+    pub mode: BackgroundMode,
+    /// The output to change the wallpaper of.
+    pub output: String,
+    /// The image to start from. The others are taken from its directory.
+    pub image: PathBuf,
+}
+
+impl RotateOpts {
+    fn args(&self) -> Vec<String> {
+        vec![
+            "rotate-wallpaper".into(),
+            "--interval".into(),
+            self.interval.to_string(),
+            "--mode".into(),
+            self.mode.to_string(),
+            self.output.clone(),
+            self.image.to_string_lossy().into_owned(),
+        ]
+    }
+
+    /// The sway config line which starts the rotation.
+    pub fn config(&self) -> String {
+        let exe = std::env::current_exe().unwrap_or_else(|_| "twl-configurator".into());
+        let mut line = format!("exec_always {}", quote_arg(&exe.to_string_lossy()));
+        for a in self.args() {
+            line.push(' ');
+            line.push_str(&quote_arg(&a));
+        }
+        line
+    }
+
+    /// Parses a config line written by `config`.
+    pub fn from_config(line: &str) -> Option<RotateOpts> {
+        let line = line.trim().strip_prefix("exec_always ")?;
+        let args = split_args(line);
+        let start = args.iter().position(|a| a == "rotate-wallpaper")?;
+        RotateOpts::from_iter_safe(&args[start..]).ok()
+    }
+
+    pub fn from_background(output: &str, bg: &Background) -> Option<RotateOpts> {
+        if !bg.rotates() {
+            return None;
+        }
+        Some(RotateOpts {
+            interval: bg.rotate_minutes,
+            mode: bg.mode,
+            output: output.to_string(),
+            image: bg.source.clone().into(),
+        })
+    }
+}
+
+/// Lists the images directly inside `dir`, sorted by path.
+pub fn list_images(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut images = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_image = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map_or(false, |e| {
+                IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str())
+            });
+        if is_image {
+            images.push(path);
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+fn pid_file(output: &str) -> PathBuf {
+    let mut path = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    path.push(format!("twl-wallpaper-{}.pid", output));
+    path
+}
+
+/// Stops the process rotating the wallpaper of the output, if there is one.
+pub fn stop_rotation(output: &str) -> Result<(), std::io::Error> {
+    let path = pid_file(output);
+    let pid = match std::fs::read_to_string(&path) {
+        Ok(pid) => pid.trim().to_string(),
+        Err(_) => return Ok(()),
+    };
+
+    // Make sure the pid wasn't reused by something else.
+    let ours = std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|c| String::from_utf8_lossy(&c).contains("rotate-wallpaper"))
+        .unwrap_or(false);
+    if ours && pid != std::process::id().to_string() {
+        std::process::Command::new("kill").arg(&pid).status()?;
+    }
+    std::fs::remove_file(&path)
+}
+
+/// Starts or stops rotating the wallpaper of the output to match `bg`.
+pub fn update_rotation(output: &str, bg: &Background) -> Result<(), std::io::Error> {
+    stop_rotation(output)?;
+    if let Some(opts) = RotateOpts::from_background(output, bg) {
+        let exe = std::env::current_exe().unwrap_or_else(|_| "twl-configurator".into());
+        std::process::Command::new(exe).args(opts.args()).spawn()?;
+    }
+    Ok(())
+}
+
+/// Cycles the wallpaper of the output through the images in the directory,
+/// replacing any previous rotation of the same output. Only returns on error.
+pub fn rotate(opts: &RotateOpts) -> Result<(), Box<dyn std::error::Error>> {
+    stop_rotation(&opts.output)?;
+    std::fs::write(pid_file(&opts.output), std::process::id().to_string())?;
+
+    let dir = opts.image.parent().unwrap_or_else(|| Path::new("."));
+    let interval = Duration::from_secs(opts.interval.max(1) as u64 * 60);
+    let mut conn = swayipc::Connection::new()?;
+    let mut current = opts.image.clone();
+
+    loop {
+        std::thread::sleep(interval);
+
+        let images = list_images(dir)?;
+        current = match images.iter().position(|i| *i == current) {
+            Some(i) => images[(i + 1) % images.len()].clone(),
+            None => match images.first() {
+                Some(i) => i.clone(),
+                None => continue,
+            },
+        };
+
+        let cmd = format!(
+            "output {} bg {} {}",
+            quote_arg(&opts.output),
+            quote_arg(&current.to_string_lossy()),
+            opts.mode
+        );
+        for r in conn.run_command(cmd)? {
+            r?;
+        }
+    }
+}
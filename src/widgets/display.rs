@@ -1,10 +1,64 @@
 use druid::piet::{FontFamily, Text, TextLayoutBuilder};
 use druid::widget::prelude::*;
-use druid::{Affine, Color};
+use druid::{Affine, Color, KbKey};
 
-use crate::model::display::Display;
+use crate::model::display::{Display, Transform};
 use druid::im::HashMap;
 
+/// Distance in screen pixels within which a dragged output snaps to the
+/// edges of the others.
+const SNAP_DISTANCE: f64 = 24.;
+
+/// Distance in logical pixels an output moves with Shift+arrow keys.
+const NUDGE_STEP: i32 = 16;
+
+/// Gap in logical pixels around the disabled outputs shown below the layout.
+const DISABLED_GAP: i32 = 200;
+
+/// The area (x1, y1, x2, y2) covered by a display in the global coordinate
+/// space, if it were placed at the given position.
+fn display_rect(d: &Display, pos: (i32, i32)) -> (i32, i32, i32, i32) {
+    let size = d.logical_size();
+    (pos.0, pos.1, pos.0 + size.0 as i32, pos.1 + size.1 as i32)
+}
+
+/// Where each output is drawn. Disabled outputs have no place in the
+/// layout, so they are lined up below it instead.
+fn shown_positions(data: &HashMap<String, Display>) -> HashMap<String, (i32, i32)> {
+    let mut shown: HashMap<String, (i32, i32)> = data
+        .values()
+        .filter(|d| d.active)
+        .map(|d| (d.name.clone(), d.position.into()))
+        .collect();
+
+    let rects: Vec<_> = data
+        .values()
+        .filter(|d| d.active)
+        .map(|d| display_rect(d, d.position.into()))
+        .collect();
+    let mut x = rects.iter().map(|r| r.0).min().unwrap_or(0);
+    let y = rects.iter().map(|r| r.3 + DISABLED_GAP).max().unwrap_or(0);
+
+    let mut disabled: Vec<&Display> = data.values().filter(|d| !d.active).collect();
+    disabled.sort_by(|a, b| a.name.cmp(&b.name));
+    for d in disabled {
+        shown.insert(d.name.clone(), (x, y));
+        x += d.logical_size().0 as i32 + DISABLED_GAP;
+    }
+    shown
+}
+
+fn overlaps(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> bool {
+    a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+}
+
+/// Whether the two areas share some length of an edge.
+fn touches(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> bool {
+    let x_span = a.0 < b.2 && b.0 < a.2;
+    let y_span = a.1 < b.3 && b.1 < a.3;
+    ((a.2 == b.0 || b.2 == a.0) && y_span) || ((a.3 == b.1 || b.3 == a.1) && x_span)
+}
+
 #[derive(Clone, Default, Debug)]
 struct DragState {
     start: (f64, f64),
@@ -30,17 +84,17 @@ impl Default for MonitorView {
 
 impl MonitorView {
     fn window_space_bounds(&self, data: &HashMap<String, Display>) -> ((f64, f64), (f64, f64)) {
+        let shown = shown_positions(data);
         data.iter().fold(
             ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN)),
-            |acc, (_, x)| {
+            |acc, (n, x)| {
+                let size = x.logical_size();
+                let pos = shown[n];
                 (
+                    (acc.0 .0.min(pos.0 as f64), acc.0 .1.min(pos.1 as f64)),
                     (
-                        acc.0 .0.min(x.position.0 as f64),
-                        acc.0 .1.min(x.position.1 as f64),
-                    ),
-                    (
-                        acc.1 .0.max(x.position.0 as f64 + x.size.0 as f64),
-                        acc.1 .1.max(x.position.1 as f64 + x.size.1 as f64),
+                        acc.1 .0.max(pos.0 as f64 + size.0 as f64),
+                        acc.1 .1.max(pos.1 as f64 + size.1 as f64),
                     ),
                 )
             },
@@ -77,13 +131,16 @@ impl MonitorView {
 
     fn hit_test(&self, data: &HashMap<String, Display>, pos: druid::Point) -> Option<String> {
         let coords = self.unscale_coords(pos.into());
+        let shown = shown_positions(data);
 
-        for (_, d) in data {
+        for (n, d) in data {
+            let size = d.logical_size();
+            let pos = shown[n];
             let bb = druid::Rect::new(
-                d.position.0 as f64,
-                d.position.1 as f64,
-                d.position.0 as f64 + d.size.0 as f64,
-                d.position.1 as f64 + d.size.1 as f64,
+                pos.0 as f64,
+                pos.1 as f64,
+                pos.0 as f64 + size.0 as f64,
+                pos.1 as f64 + size.1 as f64,
             );
             if bb.contains(coords.into()) {
                 return Some(d.name.clone());
@@ -99,21 +156,200 @@ impl MonitorView {
         }
     }
 
+    /// Moves the layout so that it starts at (0, 0). Disabled outputs are
+    /// left where they are, as they aren't part of it.
     fn normalize_coords(&self, data: &mut HashMap<String, Display>) {
-        let (min, _) = self.window_space_bounds(data);
+        let min = data
+            .values()
+            .filter(|d| d.active)
+            .map(|d| (d.position.0, d.position.1))
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1)));
+        let min = match min {
+            Some(min) => min,
+            None => return,
+        };
+
+        for (_, d) in data.iter_mut().filter(|(_, d)| d.active) {
+            d.position = (d.position.0 - min.0, d.position.1 - min.1).into();
+        }
+    }
 
-        for (_, d) in data.iter_mut() {
-            d.position = (d.position.0 - min.0 as i32, d.position.1 - min.1 as i32).into();
+    fn other_rects(
+        &self,
+        data: &HashMap<String, Display>,
+        target: &String,
+    ) -> Vec<(i32, i32, i32, i32)> {
+        data.iter()
+            .filter(|(n, d)| *n != target && d.active)
+            .map(|(_, d)| display_rect(d, d.position.into()))
+            .collect()
+    }
+
+    /// Snaps a position for the target output to the edges and corners of
+    /// the other outputs, if any are close enough.
+    fn snap(
+        &self,
+        data: &HashMap<String, Display>,
+        target: &String,
+        pos: (i32, i32),
+    ) -> (i32, i32) {
+        let (w, h) = match data.get(target) {
+            Some(d) => d.logical_size(),
+            None => return pos,
+        };
+        let (w, h) = (w as i32, h as i32);
+        let threshold = (SNAP_DISTANCE / self.scale) as i32;
+
+        let (mut best_x, mut best_y) = ((pos.0, threshold + 1), (pos.1, threshold + 1));
+        for o in self.other_rects(data, target) {
+            for x in [o.2, o.0 - w, o.0, o.2 - w] {
+                if (x - pos.0).abs() < best_x.1 {
+                    best_x = (x, (x - pos.0).abs());
+                }
+            }
+            for y in [o.3, o.1 - h, o.1, o.3 - h] {
+                if (y - pos.1).abs() < best_y.1 {
+                    best_y = (y, (y - pos.1).abs());
+                }
+            }
         }
+
+        (best_x.0, best_y.0)
     }
 
-    fn quantize(&self, pos: (i32, i32)) -> (i32, i32) {
-        const QUANT: i32 = 16;
+    /// Names of the outputs in the order keyboard focus moves through them:
+    /// left to right, then top to bottom.
+    fn focus_order(&self, data: &HashMap<String, Display>) -> Vec<String> {
+        let shown = shown_positions(data);
+        let mut order: Vec<String> = data.keys().cloned().collect();
+        order.sort_by_key(|n| (shown[n], n.clone()));
+        order
+    }
 
-        (
-            ((pos.0 / QUANT) * QUANT) + (QUANT / 2),
-            ((pos.1 / QUANT) * QUANT) + (QUANT / 2),
-        )
+    /// Moves output focus forwards or backwards, returning false if focus
+    /// should leave the widget instead.
+    fn cycle_focus(&self, data: &mut HashMap<String, Display>, backwards: bool) -> bool {
+        let order = self.focus_order(data);
+        let current = order
+            .iter()
+            .position(|n| data.get(n).map_or(false, |d| d.focused));
+
+        let next = match (current, backwards) {
+            (None, false) => Some(0),
+            (None, true) => order.len().checked_sub(1),
+            (Some(i), false) if i + 1 < order.len() => Some(i + 1),
+            (Some(i), true) if i > 0 => Some(i - 1),
+            _ => None,
+        };
+
+        match next {
+            Some(i) => {
+                self.update_focus(data, &order[i]);
+                true
+            }
+            None => {
+                self.update_focus(data, &"".to_string());
+                false
+            }
+        }
+    }
+
+    /// The nearest position in the direction (dx, dy) where an edge of the
+    /// target output lines up with an edge of another output.
+    fn next_edge(
+        &self,
+        data: &HashMap<String, Display>,
+        target: &String,
+        (dx, dy): (i32, i32),
+    ) -> Option<(i32, i32)> {
+        let d = data.get(target)?;
+        let pos: (i32, i32) = d.position.into();
+        let (w, h) = d.logical_size();
+        let (w, h) = (w as i32, h as i32);
+
+        let others = self.other_rects(data, target);
+        let candidates = others.iter().flat_map(|o| {
+            if dx != 0 {
+                [o.0 - w, o.0, o.2 - w, o.2]
+            } else {
+                [o.1 - h, o.1, o.3 - h, o.3]
+            }
+        });
+
+        if dx != 0 {
+            candidates
+                .filter(|x| (x - pos.0) * dx > 0)
+                .min_by_key(|x| (x - pos.0).abs())
+                .map(|x| (x, pos.1))
+        } else {
+            candidates
+                .filter(|y| (y - pos.1) * dy > 0)
+                .min_by_key(|y| (y - pos.1).abs())
+                .map(|y| (pos.0, y))
+        }
+    }
+
+    /// Moves the target output to the nearest position where it does not
+    /// overlap any other output, and shares an edge with at least one.
+    fn settle(&self, data: &mut HashMap<String, Display>, target: &String) {
+        let others = self.other_rects(data, target);
+        let d = match data.get(target) {
+            Some(d) => d,
+            None => return,
+        };
+        let pos: (i32, i32) = d.position.into();
+        let (w, h) = d.logical_size();
+        let (w, h) = (w as i32, h as i32);
+
+        let current = display_rect(d, pos);
+        if others.is_empty()
+            || (!others.iter().any(|o| overlaps(current, *o))
+                && others.iter().any(|o| touches(current, *o)))
+        {
+            return;
+        }
+
+        let mut best: Option<((i32, i32), i64)> = None;
+        for o in others.iter() {
+            let x = pos.0.max(o.0 - w + 1).min(o.2 - 1);
+            let y = pos.1.max(o.1 - h + 1).min(o.3 - 1);
+
+            for cand in [(o.0 - w, y), (o.2, y), (x, o.1 - h), (x, o.3)] {
+                let r = display_rect(d, cand);
+                if others.iter().any(|o| overlaps(r, *o)) {
+                    continue;
+                }
+
+                let (dx, dy) = ((cand.0 - pos.0) as i64, (cand.1 - pos.1) as i64);
+                let dist = dx * dx + dy * dy;
+                if best.map_or(true, |(_, b)| dist < b) {
+                    best = Some((cand, dist));
+                }
+            }
+        }
+
+        if let (Some((p, _)), Some(d)) = (best, data.get_mut(target)) {
+            d.position = p.into();
+        }
+    }
+}
+
+/// Where the panel's own top edge ends up in the layout, as its own top-left
+/// and top-right corners within `rect`.
+fn native_top_edge(transform: Transform, rect: druid::Rect) -> (druid::Point, druid::Point) {
+    // Clockwise from the top-left, so each quarter turn moves one along.
+    let corners = [
+        druid::Point::new(rect.x0, rect.y0),
+        druid::Point::new(rect.x1, rect.y0),
+        druid::Point::new(rect.x1, rect.y1),
+        druid::Point::new(rect.x0, rect.y1),
+    ];
+    let turns = transform.quarter_turns() as usize;
+    let (left, right) = (corners[turns % 4], corners[(turns + 1) % 4]);
+    if transform.is_flipped() {
+        (right, left)
+    } else {
+        (left, right)
     }
 }
 
@@ -135,7 +371,7 @@ impl Widget<HashMap<String, Display>> for MonitorView {
                     match &self.dragging {
                         None => {
                             match target {
-                                Some(n) => {
+                                Some(n) if data.get(&n).unwrap().active => {
                                     let pos = data.get(&n).unwrap().position;
                                     self.update_focus(data, &n);
                                     self.dragging = Some(DragState {
@@ -143,6 +379,15 @@ impl Widget<HashMap<String, Display>> for MonitorView {
                                         target: Some((n, pos.into())),
                                     });
                                 }
+                                // Disabled outputs have no place in the
+                                // layout to drag them to.
+                                Some(n) => {
+                                    self.update_focus(data, &n);
+                                    self.dragging = Some(DragState {
+                                        start: (e.pos - self.offset).into(),
+                                        target: None,
+                                    });
+                                }
                                 None => {
                                     self.dragging = Some(DragState {
                                         start: (e.pos - self.offset).into(),
@@ -162,7 +407,8 @@ impl Widget<HashMap<String, Display>> for MonitorView {
                 if ctx.is_active() && !ctx.is_disabled() {
                     match &self.dragging {
                         Some(drag) => {
-                            if drag.target.is_some() {
+                            if let Some((target, _)) = &drag.target {
+                                self.settle(data, target);
                                 self.normalize_coords(data);
                             }
                             self.dragging = None;
@@ -178,12 +424,17 @@ impl Widget<HashMap<String, Display>> for MonitorView {
                     if let Some((target, start_pos)) = &drag.target {
                         let (sp_x, sp_y) = (start_pos.0, start_pos.1);
 
-                        data.get_mut(target).unwrap().position = self
-                            .quantize((
+                        let pos = self.snap(
+                            data,
+                            target,
+                            (
                                 (sp_x as f64 - (drag.start.0 as f64 - e.pos.x) / self.scale) as i32,
                                 (sp_y as f64 - (drag.start.1 as f64 - e.pos.y) / self.scale) as i32,
-                            ))
-                            .into();
+                            ),
+                        );
+                        if let Some(d) = data.get_mut(target) {
+                            d.position = pos.into();
+                        }
                     } else {
                         self.offset = (-(drag.start.0 - e.pos.x), -(drag.start.1 - e.pos.y));
                     }
@@ -191,26 +442,91 @@ impl Widget<HashMap<String, Display>> for MonitorView {
                 }
                 None => {}
             },
+            Event::KeyDown(k) if ctx.is_focused() && self.dragging.is_none() => {
+                let dir = match &k.key {
+                    KbKey::Tab => {
+                        if self.cycle_focus(data, k.mods.shift()) {
+                            ctx.set_handled();
+                        } else if k.mods.shift() {
+                            ctx.focus_prev();
+                        } else {
+                            ctx.focus_next();
+                        }
+                        ctx.request_paint();
+                        return;
+                    }
+                    KbKey::ArrowLeft => (-1, 0),
+                    KbKey::ArrowRight => (1, 0),
+                    KbKey::ArrowUp => (0, -1),
+                    KbKey::ArrowDown => (0, 1),
+                    _ => return,
+                };
+
+                let (target, pos): (String, (i32, i32)) =
+                    match data.values().find(|d| d.focused && d.active) {
+                        Some(d) => (d.name.clone(), d.position.into()),
+                        None => return,
+                    };
+                let pos = if k.mods.ctrl() {
+                    match self.next_edge(data, &target, dir) {
+                        Some(p) => p,
+                        None => return,
+                    }
+                } else {
+                    let step = if k.mods.shift() { NUDGE_STEP } else { 1 };
+                    (pos.0 + dir.0 * step, pos.1 + dir.1 * step)
+                };
+
+                if let Some(d) = data.get_mut(&target) {
+                    d.position = pos.into();
+                }
+                // Like a drag, don't leave it overlapping or off on its own.
+                self.settle(data, &target);
+                self.normalize_coords(data);
+                ctx.set_handled();
+                ctx.request_layout();
+            }
             _ => (),
         }
     }
 
     fn lifecycle(
         &mut self,
-        _ctx: &mut LifeCycleCtx,
-        _event: &LifeCycle,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
         _data: &HashMap<String, Display>,
         _env: &Env,
     ) {
+        match event {
+            LifeCycle::BuildFocusChain => ctx.register_for_focus(),
+            LifeCycle::FocusChanged(_) => ctx.request_paint(),
+            _ => (),
+        }
     }
 
     fn update(
         &mut self,
         ctx: &mut UpdateCtx,
         _old_data: &HashMap<String, Display>,
-        _data: &HashMap<String, Display>,
+        data: &HashMap<String, Display>,
         _env: &Env,
     ) {
+        // The output being dragged may have been unplugged.
+        if let Some(DragState {
+            target: Some((name, _)),
+            ..
+        }) = &self.dragging
+        {
+            if !data.contains_key(name) {
+                self.dragging = None;
+            }
+        }
+
+        // Output sizes may have changed, so re-fit the view unless that
+        // would move things out from under the cursor.
+        if self.dragging.is_none() {
+            ctx.request_layout();
+        }
         ctx.request_paint();
     }
 
@@ -235,24 +551,46 @@ impl Widget<HashMap<String, Display>> for MonitorView {
         let size = ctx.size();
         let rect = size.to_rect();
         ctx.fill(rect, &env.get(druid::theme::WINDOW_BACKGROUND_COLOR));
+        if ctx.is_focused() {
+            ctx.stroke(rect.inset(-1.), &env.get(druid::theme::PRIMARY_LIGHT), 2.);
+        }
 
+        let shown = shown_positions(data);
         ctx.with_save(|ctx| {
             ctx.transform(Affine::translate(self.offset));
             ctx.transform(Affine::scale(self.scale));
 
-            for (_id, d) in data.iter() {
-                let (x1, y1) = (d.position.0 as f64, d.position.1 as f64);
-                let (x2, y2) = (x1 + d.size.0 as f64, y1 + d.size.1 as f64);
+            for (name, d) in data.iter() {
+                let pos = shown[name];
+                let (x1, y1) = (pos.0 as f64, pos.1 as f64);
+                let size = d.logical_size();
+                let (x2, y2) = (x1 + size.0 as f64, y1 + size.1 as f64);
                 let id = d.id.clone().unwrap_or(12);
                 let name = d.name.clone();
                 let scale = self.scale;
                 let focused = d.focused;
+                let active = d.active;
+                let inset = 12. / self.scale;
+                let (top_left, top_right) = native_top_edge(
+                    d.transform,
+                    druid::Rect::new(x1 + inset, y1 + inset, x2 - inset, y2 - inset),
+                );
+                // Unapplied edits matter more than unsaved ones.
+                let badge_color = match (d.unapplied, d.unsaved) {
+                    (true, _) => Some(Color::rgb8(230, 140, 30)),
+                    (false, true) => Some(Color::rgb8(70, 130, 200)),
+                    (false, false) => None,
+                };
 
-                let fill_color = env.get(druid::theme::BACKGROUND_DARK);
-                let stroke_color = if focused {
-                    env.get(druid::theme::TEXT_COLOR)
+                let fill_color = if active {
+                    env.get(druid::theme::BACKGROUND_DARK)
                 } else {
-                    env.get(druid::theme::PRIMARY_DARK)
+                    env.get(druid::theme::WINDOW_BACKGROUND_COLOR)
+                };
+                let stroke_color = match (focused, active) {
+                    (true, _) => env.get(druid::theme::TEXT_COLOR),
+                    (false, true) => env.get(druid::theme::PRIMARY_DARK),
+                    (false, false) => env.get(druid::theme::BORDER_DARK),
                 };
 
                 ctx.paint_with_z_index(id as u32, move |ctx| {
@@ -263,14 +601,44 @@ impl Widget<HashMap<String, Display>> for MonitorView {
 
                     let text = ctx.text();
                     let layout = text
-                        .new_text_layout(name)
+                        .new_text_layout(if active {
+                            name
+                        } else {
+                            format!("{} (disabled)", name)
+                        })
                         .font(FontFamily::SERIF, 18.0 / scale)
-                        .text_color(Color::rgb8(255, 255, 255))
+                        .text_color(if active {
+                            Color::rgb8(255, 255, 255)
+                        } else {
+                            Color::rgb8(150, 150, 150)
+                        })
                         .build()
                         .unwrap();
 
                     ctx.clip(rect);
                     ctx.draw_text(&layout, (x1 + 35.0, y1 + 35.0));
+
+                    // A bar along the panel's own top edge, starting from a
+                    // dot in its own top-left corner.
+                    let marker_color = Color::rgba8(255, 255, 255, 90);
+                    let end = top_left + (top_right - top_left) / 3.;
+                    ctx.stroke(
+                        druid::kurbo::Line::new(top_left, end),
+                        &marker_color,
+                        4.0 / scale,
+                    );
+                    ctx.fill(
+                        druid::kurbo::Circle::new(top_left, 6.0 / scale),
+                        &marker_color,
+                    );
+
+                    if let Some(color) = badge_color {
+                        let r = 8.0 / scale;
+                        ctx.fill(
+                            druid::kurbo::Circle::new((x2 - 35.0 - r, y1 + 35.0 + r), r),
+                            &color,
+                        );
+                    }
                 });
             }
         });
@@ -0,0 +1,84 @@
+use druid::widget::prelude::*;
+
+/// Gives its child a value worked out from the data, like a lens, but only
+/// works it out again when the data changes rather than on every access.
+/// Edits the child makes to the value are handed to `put`.
+pub struct Memo<T, U, W> {
+    get: Box<dyn Fn(&T) -> U>,
+    put: Box<dyn Fn(&mut T, U)>,
+    value: Option<U>,
+    /// Whether the child edited the value since it was worked out.
+    edited: bool,
+    child: W,
+}
+
+impl<T: Data, U: Data, W: Widget<U>> Memo<T, U, W> {
+    pub fn new(
+        child: W,
+        get: impl Fn(&T) -> U + 'static,
+        put: impl Fn(&mut T, U) + 'static,
+    ) -> Self {
+        Memo {
+            get: Box::new(get),
+            put: Box::new(put),
+            value: None,
+            edited: false,
+            child,
+        }
+    }
+
+    /// A memo whose child only reads the value.
+    pub fn read_only(child: W, get: impl Fn(&T) -> U + 'static) -> Self {
+        Memo::new(child, get, |_, _| {})
+    }
+
+    fn parts(&mut self, data: &T) -> (&mut W, &U) {
+        let get = &self.get;
+        let value = self.value.get_or_insert_with(|| get(data));
+        (&mut self.child, value)
+    }
+}
+
+impl<T: Data, U: Data, W: Widget<U>> Widget<T> for Memo<T, U, W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        let (child, value) = self.parts(data);
+        let mut edited = value.clone();
+        child.event(ctx, event, &mut edited, env);
+        if !edited.same(value) {
+            (self.put)(data, edited.clone());
+            self.value = Some(edited);
+            self.edited = true;
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        let (child, value) = self.parts(data);
+        child.lifecycle(ctx, event, value, env)
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
+        let old = match self.value.take() {
+            Some(v) => v,
+            None => (self.get)(old_data),
+        };
+        let new = if self.edited || !old_data.same(data) {
+            (self.get)(data)
+        } else {
+            old.clone()
+        };
+        self.edited = false;
+
+        self.child.update(ctx, &old, &new, env);
+        self.value = Some(new);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let (child, value) = self.parts(data);
+        child.layout(ctx, bc, value, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        let (child, value) = self.parts(data);
+        child.paint(ctx, value, env)
+    }
+}
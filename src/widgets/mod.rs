@@ -1 +1,4 @@
 pub mod display;
+pub mod memo;
+pub mod notifications;
+pub mod thumbnail;
@@ -0,0 +1,68 @@
+use druid::widget::prelude::*;
+use druid::widget::{
+    Button, Controller, CrossAxisAlignment, Flex, Label, LineBreaking, List, MainAxisAlignment,
+    Painter,
+};
+use druid::{Color, WidgetExt};
+
+use crate::model::notification::{commands, Level, Notification, Notifications};
+
+struct DismissController;
+
+impl<W: Widget<Notifications>> Controller<Notifications, W> for DismissController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut Notifications,
+        env: &Env,
+    ) {
+        if let Event::Command(c) = event {
+            if let Some(id) = c.get(commands::DISMISS_NOTIFICATION) {
+                data.dismiss(*id);
+                ctx.set_handled();
+            }
+        }
+
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn build_notification() -> impl Widget<Notification> {
+    Flex::row()
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_flex_child(
+            Label::new(|n: &Notification, _env: &_| n.message.clone())
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .expand_width(),
+            1.,
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new("Dismiss").on_click(|ctx, data: &mut Notification, _env| {
+                ctx.submit_command(commands::DISMISS_NOTIFICATION.with(data.id))
+            }),
+        )
+        .padding(4.)
+        .background(Painter::new(|ctx, data: &Notification, _env| {
+            let bounds = ctx.size().to_rect();
+            ctx.fill(
+                bounds,
+                &match data.level {
+                    Level::Info => Color::rgb8(30, 60, 90),
+                    Level::Error => Color::rgb8(110, 30, 30),
+                },
+            );
+        }))
+}
+
+/// A list of banners for the notifications, each of which can be dismissed.
+pub fn build_notifications() -> impl Widget<Notifications> {
+    List::new(build_notification)
+        .with_spacing(2.)
+        .lens(Notifications::items)
+        .controller(DismissController {})
+}
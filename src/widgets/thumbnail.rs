@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use druid::widget::prelude::*;
+use druid::widget::{FillStrat, Image, Label};
+use druid::{Affine, ExtEventSink, ImageBuf, Selector, Target, Vec2};
+
+/// Sent to a thumbnail once the image at the path has been decoded.
+const THUMBNAIL_LOADED: Selector<String> = Selector::new("thumbnail_loaded");
+
+const THUMBNAIL_WIDTH: u32 = 192;
+const THUMBNAIL_HEIGHT: u32 = 108;
+
+/// Decoded thumbnails by path, shared by the thumbnails of a list. `None`
+/// marks an image which couldn't be loaded.
+pub type ThumbnailCache = Arc<Mutex<HashMap<String, Option<ImageBuf>>>>;
+
+enum State {
+    Loading,
+    Failed,
+    Loaded(Image),
+}
+
+/// A downscaled copy of the image at the path in the data. Images are
+/// decoded on a background thread, showing a placeholder until they are
+/// ready.
+pub struct Thumbnail {
+    cache: ThumbnailCache,
+    path: Option<String>,
+    state: State,
+    loading: Label<String>,
+    failed: Label<String>,
+    label_size: Size,
+}
+
+impl Thumbnail {
+    pub fn new(cache: ThumbnailCache) -> Self {
+        Thumbnail {
+            cache,
+            path: None,
+            state: State::Loading,
+            loading: Label::new("Loading…"),
+            failed: Label::new("Can't load"),
+            label_size: Size::ZERO,
+        }
+    }
+
+    /// Shows the cached thumbnail of the path, returning false if it
+    /// hasn't been decoded yet.
+    fn show_cached(&mut self, path: &str) -> bool {
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        self.state = match cached {
+            Some(Some(img)) => State::Loaded(Image::new(img).fill_mode(FillStrat::Cover)),
+            Some(None) => State::Failed,
+            None => return false,
+        };
+        true
+    }
+
+    fn load(&mut self, path: &str, sink: ExtEventSink, id: WidgetId) {
+        self.path = Some(path.to_string());
+        if self.show_cached(path) {
+            return;
+        }
+
+        self.state = State::Loading;
+        let cache = self.cache.clone();
+        let path = path.to_string();
+        std::thread::spawn(move || {
+            let img = druid::image::open(&path).ok().map(|img| {
+                ImageBuf::from_dynamic_image(img.thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT))
+            });
+            cache.lock().unwrap().insert(path.clone(), img);
+            // The thumbnail may be gone by now, which is fine.
+            let _ = sink.submit_command(THUMBNAIL_LOADED, path, Target::Widget(id));
+        });
+    }
+
+    fn label(&mut self) -> Option<&mut Label<String>> {
+        match self.state {
+            State::Loading => Some(&mut self.loading),
+            State::Failed => Some(&mut self.failed),
+            State::Loaded(_) => None,
+        }
+    }
+}
+
+impl Widget<String> for Thumbnail {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut String, _env: &Env) {
+        if let Event::Command(c) = event {
+            if let Some(path) = c.get(THUMBNAIL_LOADED) {
+                if self.path.as_ref() == Some(path) {
+                    self.show_cached(path);
+                    ctx.request_layout();
+                }
+                ctx.set_handled();
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &String, env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.load(data, ctx.get_external_handle(), ctx.widget_id());
+        }
+        self.loading.lifecycle(ctx, event, data, env);
+        self.failed.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &String, data: &String, _env: &Env) {
+        if old_data != data {
+            self.load(data, ctx.get_external_handle(), ctx.widget_id());
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &String,
+        env: &Env,
+    ) -> Size {
+        if let State::Loaded(image) = &mut self.state {
+            return image.layout(ctx, bc, data, env);
+        }
+        if let Some(label) = self.label() {
+            let size = label.layout(ctx, &bc.loosen(), data, env);
+            self.label_size = size;
+        }
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &String, env: &Env) {
+        if let State::Loaded(image) = &mut self.state {
+            return image.paint(ctx, data, env);
+        }
+        let size = ctx.size();
+        let offset = Vec2::new(
+            (size.width - self.label_size.width) / 2.,
+            (size.height - self.label_size.height) / 2.,
+        );
+        if let Some(label) = self.label() {
+            ctx.with_save(|ctx| {
+                ctx.transform(Affine::translate(offset));
+                label.paint(ctx, data, env);
+            });
+        }
+    }
+}